@@ -1,5 +1,6 @@
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::Path;
 use gl_generator::{Api, Fallbacks, Profile, Registry, StaticGenerator};
 
@@ -8,9 +9,38 @@ fn main() {
     println!("cargo:rustc-link-lib=GL");
     println!("cargo:rustc-link-lib=GLX");
     let dest = env::var("OUT_DIR").unwrap();
-    let mut file = File::create(&Path::new(&dest).join("bindings.rs")).unwrap();
+    let mut file = File::create(Path::new(&dest).join("bindings.rs")).unwrap();
 
     Registry::new(Api::Gl, (4, 5), Profile::Core, Fallbacks::All, [])
         .write_bindings(StaticGenerator, &mut file)
         .unwrap();
+
+    embed_shaders(&dest);
+}
+
+/// Writes the table of embedded shader sources, keyed by their path relative to the crate root.
+/// Without the `embedded-shaders` feature the table is empty and shaders are read from disk.
+fn embed_shaders(dest: &str) {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=shaders");
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let mut paths = Vec::new();
+    if env::var_os("CARGO_FEATURE_EMBEDDED_SHADERS").is_some() {
+        for entry in fs::read_dir(Path::new(&root).join("shaders")).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                println!("cargo:rerun-if-changed={}", path.display());
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+
+    let mut file = File::create(Path::new(dest).join("shaders.rs")).unwrap();
+    writeln!(file, "pub static SHADERS: &[(&str, &str)] = &[").unwrap();
+    for path in paths {
+        let name = format!("shaders/{}", path.file_name().unwrap().to_str().unwrap());
+        writeln!(file, "    ({:?}, include_str!({:?})),", name, path.to_str().unwrap()).unwrap();
+    }
+    writeln!(file, "];").unwrap();
 }
\ No newline at end of file
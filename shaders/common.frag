@@ -1,5 +1,83 @@
 #version 460
 
+#define MAX_LIGHTS 8
+#define MAX_SHADOW_MAPS 4
+#define POINT_LIGHT 0
+#define DIRECTIONAL_LIGHT 1
+#define SPOT_LIGHT 2
+
+#include "frame.glsl"
+
+struct Light {
+    int kind;
+    vec3 position;
+    vec3 direction;
+    vec3 color;
+    float intensity;
+    float innerCutoff;
+    float outerCutoff;
+    int shadowMap;
+};
+
+uniform Light lights[MAX_LIGHTS];
+uniform int lightCount;
+
+uniform mat4 lightSpace[MAX_SHADOW_MAPS];
+uniform sampler2DShadow shadowMaps[MAX_SHADOW_MAPS];
+uniform bool receiveShadows;
+
 vec3 id(vec3 x) {
     return x;
+}
+
+// Fraction of light reaching `position` through shadow map `index`, 3x3 PCF filtered.
+float shadowFactor(int index, vec3 position, vec3 n, vec3 l) {
+    vec4 clip = lightSpace[index] * vec4(position, 1.0);
+    vec3 coords = clip.xyz / clip.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+    float bias = max(0.002 * (1.0 - dot(n, l)), 0.0002);
+    vec2 texel = 1.0 / vec2(textureSize(shadowMaps[index], 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadowMaps[index], vec3(coords.xy + vec2(x, y) * texel, coords.z - bias));
+        }
+    }
+    return lit / 9.0;
+}
+
+// Blinn-Phong shading of a surface point. Unlit scenes keep the plain albedo.
+vec3 blinnPhong(vec3 position, vec3 normal, vec3 albedo) {
+    if (lightCount == 0) {
+        return albedo;
+    }
+    vec3 n = normalize(normal);
+    vec3 v = normalize(cameraPosition - position);
+    vec3 result = 0.1 * albedo;
+    for (int i = 0; i < lightCount && i < MAX_LIGHTS; i++) {
+        vec3 l;
+        float attenuation = 1.0;
+        if (lights[i].kind == DIRECTIONAL_LIGHT) {
+            l = normalize(-lights[i].direction);
+        } else {
+            vec3 toLight = lights[i].position - position;
+            float distance = length(toLight);
+            l = toLight / distance;
+            attenuation = 1.0 / (1.0 + 0.09 * distance + 0.032 * distance * distance);
+            if (lights[i].kind == SPOT_LIGHT) {
+                float theta = dot(-l, normalize(lights[i].direction));
+                float edge = max(lights[i].innerCutoff - lights[i].outerCutoff, 1e-4);
+                attenuation *= clamp((theta - lights[i].outerCutoff) / edge, 0.0, 1.0);
+            }
+        }
+        if (receiveShadows && lights[i].shadowMap >= 0) {
+            attenuation *= shadowFactor(lights[i].shadowMap, position, n, l);
+        }
+        float diffuse = max(dot(n, l), 0.0);
+        float specular = diffuse > 0.0 ? pow(max(dot(n, normalize(l + v)), 0.0), 32.0) : 0.0;
+        result += (diffuse * albedo + 0.5 * specular) * lights[i].color * lights[i].intensity * attenuation;
+    }
+    return result;
 }
\ No newline at end of file
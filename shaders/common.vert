@@ -1,5 +1,7 @@
 #version 460
 
+#include "frame.glsl"
+
 vec3 id(vec3 x) {
     return x;
 }
\ No newline at end of file
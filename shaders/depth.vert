@@ -0,0 +1,10 @@
+#version 460
+
+layout (location = 0) in vec3 vPos;
+
+uniform mat4 model;
+uniform mat4 shadowLightSpace;
+
+void main() {
+    gl_Position = shadowLightSpace * model * vec4(vPos, 1.0);
+}
\ No newline at end of file
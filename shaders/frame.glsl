@@ -0,0 +1,8 @@
+// Per-frame data, filled once per frame by `frame::begin`. Matches `FrameData` in src/frame.rs.
+layout(std140, row_major, binding = 0) uniform Frame {
+    mat4 view;
+    mat4 projection;
+    vec3 cameraPosition;
+    float time;
+    vec2 viewport;
+};
@@ -1,10 +1,19 @@
 #version 330 core
 
 in vec3 normal;
+in vec3 worldPosition;
+in vec3 worldNormal;
 
-out vec4 color;
+uniform vec4 color;
+
+out vec4 fragColor;
+
+#include "common.frag"
 
 void main()
 {
-    color = vec4(normal, 1.0);
+    // Normals run from -1 to 1; remapped to 0..1 so no side of the sphere gets a negative,
+    // and so black, albedo that hides the tint.
+    vec4 albedo = vec4(normal * 0.5 + 0.5, 1.0) * color;
+    fragColor = vec4(blinnPhong(worldPosition, worldNormal, albedo.rgb), albedo.a);
 }
\ No newline at end of file
@@ -3,13 +3,19 @@
 layout (location = 0) in vec3 vPos;
 layout (location = 1) in vec3 nPos;
 
+#include "common.vert"
+
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+uniform mat4 normalMatrix;
 
 out vec3 normal;
+out vec3 worldPosition;
+out vec3 worldNormal;
 
 void main() {
-    gl_Position = projection * view * model * vec4(vPos, 1.0);
+    vec4 world = model * vec4(vPos, 1.0);
+    gl_Position = projection * view * world;
     normal = nPos;
+    worldPosition = world.xyz;
+    worldNormal = mat3(normalMatrix) * nPos;
 }
\ No newline at end of file
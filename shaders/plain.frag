@@ -0,0 +1,14 @@
+#version 460
+
+in vec3 worldPosition;
+in vec3 worldNormal;
+
+uniform vec4 color;
+
+out vec4 fragColor;
+
+#include "common.frag"
+
+void main() {
+    fragColor = vec4(blinnPhong(worldPosition, worldNormal, color.rgb), color.a);
+}
\ No newline at end of file
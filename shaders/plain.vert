@@ -0,0 +1,19 @@
+#version 460
+
+layout (location = 0) in vec3 vPos;
+layout (location = 1) in vec3 nPos;
+
+#include "common.vert"
+
+uniform mat4 model;
+uniform mat4 normalMatrix;
+
+out vec3 worldPosition;
+out vec3 worldNormal;
+
+void main() {
+    vec4 world = model * vec4(vPos, 1.0);
+    gl_Position = projection * view * world;
+    worldPosition = world.xyz;
+    worldNormal = mat3(normalMatrix) * nPos;
+}
\ No newline at end of file
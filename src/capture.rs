@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use crate::gl;
+use crate::image::Image;
+
+/// Pixel buffers a `Recorder` cycles through. A frame is read back while the next ones render,
+/// so reading it rarely has to wait.
+const BUFFERS: usize = 3;
+
+/// Reads the bound read framebuffer's color buffer, waiting for drawing to finish.
+pub fn read_pixels(width: u32, height: u32) -> Image {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, width as _, height as _, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+    }
+    Image::from_gl_rgba(width, height, &pixels)
+}
+
+/// Where a `Recorder` sends frames.
+#[derive(Debug)]
+pub enum RecordingOutput {
+    /// One image per frame in `directory`, `frame_000000.png` and so on. An `extension` of `ppm`
+    /// writes PPMs instead.
+    Frames { directory: PathBuf, extension: String },
+    /// Raw RGB frames, top row first, written to the standard input of a child process. Every
+    /// frame must have the size of the first.
+    Pipe(Command),
+}
+
+impl RecordingOutput {
+    pub fn frames(directory: impl Into<PathBuf>) -> Self {
+        RecordingOutput::Frames { directory: directory.into(), extension: "png".to_string() }
+    }
+
+    /// Encodes frames of `size` shown at `fps` into the video `path` with ffmpeg, which must be
+    /// on the `PATH`.
+    pub fn ffmpeg(path: impl AsRef<Path>, size: (u32, u32), fps: u32) -> Self {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", size.0, size.1), "-r", &fps.to_string(), "-i", "-"])
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(path.as_ref());
+        RecordingOutput::Pipe(command)
+    }
+}
+
+enum Sink {
+    Frames { directory: PathBuf, extension: String },
+    Pipe { child: Child, size: Option<(u32, u32)> },
+}
+
+impl Sink {
+    /// Writes every frame received until the recorder hangs up, then closes the sink. Stops at
+    /// the first error. Returns the number of frames written.
+    fn run(mut self, frames: Receiver<Image>) -> Result<u64, String> {
+        let mut written = 0;
+        let result = frames.iter().try_for_each(|image| {
+            self.write(written, &image)?;
+            written += 1;
+            Ok(())
+        });
+        let closed = self.close();
+        result.and(closed).map(|()| written)
+    }
+
+    fn write(&mut self, index: u64, image: &Image) -> Result<(), String> {
+        match self {
+            Sink::Frames { directory, extension } => {
+                let path = directory.join(format!("frame_{:06}.{}", index, extension));
+                image.save(&path).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+            }
+            Sink::Pipe { child, size } => {
+                let frame_size = (image.width(), image.height());
+                if *size.get_or_insert(frame_size) != frame_size {
+                    return Err(format!("Recorded frames changed size to {}x{} during the recording.", frame_size.0, frame_size.1));
+                }
+                let stdin = child.stdin.as_mut().unwrap();
+                stdin.write_all(&image.rgb()).map_err(|e| format!("Couldn't write to the recording process: {}", e))
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        let Sink::Pipe { child, .. } = self else { return Ok(()) };
+        // Closing standard input ends the stream.
+        drop(child.stdin.take());
+        match child.wait() {
+            Ok(status) if !status.success() => Err(format!("The recording process failed with {}.", status)),
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Couldn't wait for the recording process: {}", e)),
+        }
+    }
+}
+
+struct Pending {
+    buffer: u32,
+    fence: gl::types::GLsync,
+    width: u32,
+    height: u32,
+}
+
+/// The thread encoding and writing frames, so that PNG encoding and slow disks or pipes don't
+/// hold up rendering.
+struct Writer {
+    frames: SyncSender<Image>,
+    thread: JoinHandle<Result<u64, String>>,
+}
+
+/// Captures every frame through pixel buffer objects. Once a transfer is done the frame is
+/// handed to a writer thread. Up to `BUFFERS` frames wait for it; beyond that rendering waits
+/// too, rather than piling up frames in memory. Errors stop the recording and are reported by
+/// `finish`.
+pub struct Recorder {
+    writer: Option<Writer>,
+    free: Vec<u32>,
+    pending: VecDeque<Pending>,
+    frames: u64,
+    error: Option<String>,
+}
+
+impl Recorder {
+    /// Needs a current context.
+    pub fn new(output: RecordingOutput) -> Result<Self, String> {
+        let sink = match output {
+            RecordingOutput::Frames { directory, extension } => {
+                fs::create_dir_all(&directory).map_err(|e| format!("Couldn't create {}: {}", directory.display(), e))?;
+                Sink::Frames { directory, extension }
+            }
+            RecordingOutput::Pipe(mut command) => {
+                let child = command
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| format!("Couldn't start {:?}: {}", command.get_program(), e))?;
+                Sink::Pipe { child, size: None }
+            }
+        };
+        let (frames, received) = mpsc::sync_channel(BUFFERS);
+        let thread = thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || sink.run(received))
+            .map_err(|e| format!("Couldn't start the recording thread: {}", e))?;
+        let mut free = vec![0; BUFFERS];
+        unsafe {
+            gl::CreateBuffers(BUFFERS as _, free.as_mut_ptr());
+        }
+        Ok(Self { writer: Some(Writer { frames, thread }), free, pending: VecDeque::new(), frames: 0, error: None })
+    }
+
+    /// Number of frames read back so far. Some of them may still be waiting to be written.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Starts reading the bound read framebuffer's color buffer, and passes on the frames whose
+    /// transfer has completed.
+    pub fn capture(&mut self, width: u32, height: u32) {
+        if self.error.is_some() {
+            return;
+        }
+        // With every buffer in flight, wait for the oldest.
+        self.collect(self.free.is_empty());
+        let Some(buffer) = self.free.pop() else { return };
+        unsafe {
+            gl::NamedBufferData(buffer, (width * height * 4) as _, core::ptr::null(), gl::STREAM_READ);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            // Returns at once; the pixels are copied into the buffer when drawing is done.
+            gl::ReadPixels(0, 0, width as _, height as _, gl::RGBA, gl::UNSIGNED_BYTE, core::ptr::null_mut());
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.pending.push_back(Pending { buffer, fence, width, height });
+        }
+    }
+
+    /// Writes the remaining frames and ends the recording. Returns the number of frames written.
+    pub fn finish(mut self) -> Result<u64, String> {
+        while !self.pending.is_empty() && self.error.is_none() {
+            self.collect(true);
+        }
+        let written = self.stop_writer();
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => written,
+        }
+    }
+
+    /// Passes on the frames whose transfer has completed, in order. With `wait`, waits for the
+    /// oldest one first.
+    fn collect(&mut self, mut wait: bool) {
+        while let Some(frame) = self.pending.front() {
+            unsafe {
+                let timeout = if wait { u64::MAX } else { 0 };
+                let status = gl::ClientWaitSync(frame.fence, gl::SYNC_FLUSH_COMMANDS_BIT, timeout);
+                if status == gl::TIMEOUT_EXPIRED {
+                    return;
+                }
+                wait = false;
+                let frame = self.pending.pop_front().unwrap();
+                gl::DeleteSync(frame.fence);
+
+                let size = (frame.width * frame.height * 4) as usize;
+                let data = gl::MapNamedBufferRange(frame.buffer, 0, size as _, gl::MAP_READ_BIT) as *const u8;
+                let image = (!data.is_null()).then(|| Image::from_gl_rgba(frame.width, frame.height, std::slice::from_raw_parts(data, size)));
+                gl::UnmapNamedBuffer(frame.buffer);
+                self.free.push(frame.buffer);
+
+                let Some(image) = image else {
+                    self.error = Some("Couldn't map a pixel buffer.".to_string());
+                    return;
+                };
+                // Fails once the writer has stopped, which only happens on an error.
+                if self.writer.as_ref().is_none_or(|writer| writer.frames.send(image).is_err()) {
+                    self.error = Some(self.stop_writer().err().unwrap_or_else(|| "The recording stopped.".to_string()));
+                    return;
+                }
+                self.frames += 1;
+            }
+        }
+    }
+
+    /// Lets the writer finish the frames it was given and waits for it.
+    fn stop_writer(&mut self) -> Result<u64, String> {
+        let Some(Writer { frames, thread }) = self.writer.take() else { return Ok(0) };
+        drop(frames);
+        thread.join().unwrap_or_else(|_| Err("The recording thread panicked.".to_string()))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        unsafe {
+            for frame in self.pending.drain(..) {
+                gl::DeleteSync(frame.fence);
+                self.free.push(frame.buffer);
+            }
+            gl::DeleteBuffers(self.free.len() as _, self.free.as_ptr());
+        }
+        let _ = self.stop_writer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{test_context, Headless};
+    use crate::state::ClearState;
+
+    /// Renders `frames` frames of different colors into `output`.
+    fn record(headless: &Headless, frames: u8, output: RecordingOutput) -> Result<u64, String> {
+        let mut recorder = Recorder::new(output)?;
+        for frame in 0..frames {
+            headless.set_clear_state(ClearState { color: [frame as f32 / 255.0, 0.0, 0.0, 1.0], ..ClearState::default() });
+            headless.next_frame();
+            let (width, height) = headless.size();
+            recorder.capture(width, height);
+        }
+        recorder.finish()
+    }
+
+    #[test]
+    fn records_numbered_frames_and_pipes() {
+        let headless = test_context(8, 4);
+        let directory = std::env::temp_dir().join(format!("capture-test-{}", std::process::id()));
+
+        let output = RecordingOutput::Frames { directory: directory.clone(), extension: "ppm".to_string() };
+        assert_eq!(record(&headless, 5, output), Ok(5));
+        for frame in 0..5 {
+            let image = Image::load_ppm(directory.join(format!("frame_{:06}.ppm", frame))).unwrap();
+            assert_eq!(image.pixel(7, 3), [frame, 0, 0, 255]);
+        }
+
+        let raw = directory.join("frames.rgb");
+        let mut cat = Command::new("cat");
+        cat.stdout(fs::File::create(&raw).unwrap());
+        assert_eq!(record(&headless, 4, RecordingOutput::Pipe(cat)), Ok(4));
+        let data = fs::read(&raw).unwrap();
+        assert_eq!(data.len(), 4 * 8 * 4 * 3);
+        assert_eq!(&data[3 * 96..3 * 96 + 3], &[3, 0, 0]);
+
+        // Errors on the writer thread end up in `finish`.
+        let error = record(&headless, 3, RecordingOutput::Pipe(Command::new("false"))).unwrap_err();
+        assert!(error.contains("recording process"), "{}", error);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
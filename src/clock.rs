@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of frames `FrameClock::stats` is computed over.
+pub const STATS_FRAMES: usize = 120;
+
+/// Frame rate and frame times over the last `STATS_FRAMES` frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    pub fps: f32,
+    pub average: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// Measures frame times and paces frames to a target rate.
+pub struct FrameClock {
+    start: Instant,
+    last: Instant,
+    delta: Duration,
+    frame: u64,
+    period: Option<Duration>,
+    deadline: Instant,
+    history: VecDeque<Duration>,
+}
+
+impl FrameClock {
+    /// `target_fps` of `None` never sleeps, e.g. when vsync already paces frames.
+    pub fn new(target_fps: Option<f32>) -> Self {
+        let now = Instant::now();
+        let mut clock = Self {
+            start: now,
+            last: now,
+            delta: Duration::ZERO,
+            frame: 0,
+            period: None,
+            deadline: now,
+            history: VecDeque::with_capacity(STATS_FRAMES),
+        };
+        clock.set_target_fps(target_fps);
+        clock
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<f32>) {
+        self.period = target_fps.filter(|&fps| fps > 0.0).map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.deadline = self.last + self.period.unwrap_or_default();
+    }
+
+    pub fn target_fps(&self) -> Option<f32> {
+        self.period.map(|p| 1.0 / p.as_secs_f32())
+    }
+
+    /// Sleeps until the next frame is due, taking the time already spent on this one into
+    /// account, then starts the next frame. Returns its delta time in seconds.
+    pub fn wait(&mut self) -> f32 {
+        if self.period.is_some() {
+            let now = Instant::now();
+            if self.deadline > now {
+                std::thread::sleep(self.deadline - now);
+            }
+        }
+        self.tick()
+    }
+
+    /// Starts the next frame without sleeping. Returns its delta time in seconds.
+    pub fn tick(&mut self) -> f32 {
+        self.advance(Instant::now());
+        self.delta()
+    }
+
+    fn advance(&mut self, now: Instant) {
+        self.delta = now - self.last;
+        self.last = now;
+        self.frame += 1;
+        if self.history.len() == STATS_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.delta);
+        if let Some(period) = self.period {
+            self.deadline += period;
+            // After a long frame, start over instead of rushing through the missed ones.
+            if self.deadline < now {
+                self.deadline = now + period;
+            }
+        }
+    }
+
+    /// Seconds between the last two frames.
+    pub fn delta(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Seconds since the clock was created.
+    pub fn elapsed(&self) -> f32 {
+        (self.last - self.start).as_secs_f32()
+    }
+
+    /// Number of frames so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// `None` before the first frame.
+    pub fn stats(&self) -> Option<FrameStats> {
+        let total = self.history.iter().sum::<Duration>();
+        let average = total.checked_div(self.history.len() as u32)?;
+        Some(FrameStats {
+            fps: if total.is_zero() { 0.0 } else { self.history.len() as f32 / total.as_secs_f32() },
+            average,
+            min: *self.history.iter().min()?,
+            max: *self.history.iter().max()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn reports_delta_and_stats() {
+        let mut clock = FrameClock::new(None);
+        assert_eq!(clock.stats(), None);
+        let start = clock.last;
+        for (i, frame) in [10, 20, 30].into_iter().enumerate() {
+            let now = clock.last + ms(frame);
+            clock.advance(now);
+            assert_eq!(clock.frame(), i as u64 + 1);
+        }
+        assert!((clock.delta() - 0.030).abs() < 1e-6);
+        assert_eq!(clock.last - start, ms(60));
+        let stats = clock.stats().unwrap();
+        assert_eq!((stats.average, stats.min, stats.max), (ms(20), ms(10), ms(30)));
+        assert!((stats.fps - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn stats_cover_recent_frames_only() {
+        let mut clock = FrameClock::new(None);
+        clock.advance(clock.last + ms(500));
+        for _ in 0..STATS_FRAMES {
+            clock.advance(clock.last + ms(10));
+        }
+        assert_eq!(clock.stats().unwrap().max, ms(10));
+    }
+
+    #[test]
+    fn deadlines_advance_by_the_period() {
+        let mut clock = FrameClock::new(Some(100.0));
+        let first = clock.deadline;
+        // A fast frame keeps the schedule.
+        clock.advance(clock.last + ms(4));
+        assert_eq!(clock.deadline, first + ms(10));
+        // A frame slower than the period moves the schedule instead of catching up.
+        let late = clock.last + ms(50);
+        clock.advance(late);
+        assert_eq!(clock.deadline, late + ms(10));
+        assert!((clock.target_fps().unwrap() - 100.0).abs() < 1e-3);
+    }
+}
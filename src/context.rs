@@ -0,0 +1,101 @@
+use std::ffi::CStr;
+use std::fmt;
+use crate::gl;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlProfile {
+    Core,
+    Compatibility,
+}
+
+/// What to ask the driver for when creating an OpenGL context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlConfig {
+    /// Major and minor version.
+    pub version: (u32, u32),
+    pub profile: GlProfile,
+    /// Creates a debug context and prints the driver's debug messages.
+    pub debug: bool,
+    /// Samples per pixel for MSAA, 0 to disable it.
+    pub samples: u32,
+    /// sRGB framebuffer, with `GL_FRAMEBUFFER_SRGB` enabled.
+    pub srgb: bool,
+}
+
+impl Default for GlConfig {
+    /// OpenGL 4.5 core, matching the bindings generated by `build.rs`.
+    fn default() -> Self {
+        Self {
+            version: (4, 5),
+            profile: GlProfile::Core,
+            debug: false,
+            samples: 0,
+            srgb: false,
+        }
+    }
+}
+
+impl fmt::Display for GlConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let profile = match self.profile {
+            GlProfile::Core => "core",
+            GlProfile::Compatibility => "compatibility",
+        };
+        write!(f, "OpenGL {}.{} {}", self.version.0, self.version.1, profile)?;
+        if self.debug {
+            write!(f, " debug")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sets up the state `config` asks for in the current context.
+pub(crate) unsafe fn apply(config: &GlConfig) {
+    if config.debug {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // Report errors from inside the offending call, so they show up in its backtrace.
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_message), core::ptr::null());
+    }
+    if config.samples > 0 {
+        gl::Enable(gl::MULTISAMPLE);
+    }
+    if config.srgb {
+        gl::Enable(gl::FRAMEBUFFER_SRGB);
+    }
+}
+
+extern "system" fn debug_message(
+    _source: gl::types::GLenum,
+    kind: gl::types::GLenum,
+    id: gl::types::GLuint,
+    severity: gl::types::GLenum,
+    _length: gl::types::GLsizei,
+    message: *const gl::types::GLchar,
+    _user: *mut core::ffi::c_void,
+) {
+    if severity == gl::DEBUG_SEVERITY_NOTIFICATION {
+        return;
+    }
+    let kind = match kind {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        _ => "message",
+    };
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    eprintln!("GL {} {}: {}", kind, id, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_the_requested_context() {
+        assert_eq!(GlConfig::default().to_string(), "OpenGL 4.5 core");
+        let config = GlConfig { version: (3, 3), profile: GlProfile::Compatibility, debug: true, ..GlConfig::default() };
+        assert_eq!(config.to_string(), "OpenGL 3.3 compatibility debug");
+    }
+}
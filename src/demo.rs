@@ -0,0 +1,45 @@
+//! The scene the binary shows, shared with the golden tests.
+
+use crate::light::Light;
+use crate::math::Vec3;
+use crate::objects::{Plane, Sphere};
+use crate::scene::{NodeId, Scene};
+use crate::shader::ShaderError;
+use crate::traits::*;
+
+/// The ground, two lights and two spheres tilted 45 degrees in opposite directions.
+pub fn build_scene() -> Result<(Scene, [NodeId; 2]), ShaderError> {
+    let mut scene = Scene::new();
+    scene.add_light(Light::directional(Vec3(-1.0, -1.0, -1.0), Vec3(1.0, 1.0, 1.0), 0.8).with_shadows());
+    scene.add_light(Light::point(Vec3(0.0, 3.0, 4.0), Vec3(1.0, 0.9, 0.7), 2.0));
+
+    let mut ground = Plane::new()?;
+    ground.scaled_by(15.0);
+    ground.set_position(Vec3(0.0, -4.0, 0.0));
+    ground.set_color(200, 200, 200);
+    scene.add("ground", ground);
+
+    let mut sphere = Sphere::new(6)?;
+    sphere.scaled_by(2.0);
+    sphere.rotate_around(45.0, Vec3::front());
+    sphere.set_position(Vec3(5.0, 0.0, 0.0));
+    sphere.set_color(255, 180, 120);
+    let sphere = scene.add("sphere", sphere);
+
+    let mut sphere2 = Sphere::new(6)?;
+    sphere2.scaled_by(2.0);
+    sphere2.rotate_around(-45.0, Vec3::front());
+    sphere2.set_position(Vec3(-5.0, 0.0, 0.0));
+    sphere2.set_color(120, 180, 255);
+    let sphere2 = scene.add("sphere2", sphere2);
+    Ok((scene, [sphere, sphere2]))
+}
+
+/// Turns the spheres by `angle` degrees, in opposite directions.
+pub fn spin(scene: &mut Scene, spheres: [NodeId; 2], angle: f32) {
+    for (sphere, angle) in spheres.into_iter().zip([angle, -angle]) {
+        let node = scene.get_mut(sphere).unwrap();
+        node.rotate_around(angle, Vec3::up());
+        node.rotate_around(angle, Vec3::front());
+    }
+}
@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+use std::time::Instant;
+use crate::gl;
+use crate::math::Camera;
+use crate::state::Viewport;
+
+/// Uniform buffer binding of the `Frame` block declared in `shaders/frame.glsl`.
+pub const FRAME_BINDING: u32 = 0;
+
+/// The `Frame` block in std140 layout. Matrices are row-major, as declared in the shaders.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameData {
+    pub view: [f32; 16],
+    pub projection: [f32; 16],
+    pub camera_position: [f32; 3],
+    /// Seconds since the first frame.
+    pub time: f32,
+    pub viewport: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl FrameData {
+    pub fn new(camera: &Camera, time: f32, viewport: (f32, f32)) -> Self {
+        let position = camera.position();
+        Self {
+            view: camera.view.0,
+            projection: camera.projection.0,
+            camera_position: [position.x(), position.y(), position.z()],
+            time,
+            viewport: [viewport.0, viewport.1],
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Uniform buffer holding the data shared by every draw in a frame.
+pub struct FrameUniforms {
+    buffer: u32,
+    start: Instant,
+}
+
+thread_local! {
+    static FRAME_UNIFORMS: RefCell<Option<FrameUniforms>> = const { RefCell::new(None) };
+}
+
+impl FrameUniforms {
+    pub fn new() -> Self {
+        unsafe {
+            let mut buffer = 0;
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, buffer);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                std::mem::size_of::<FrameData>() as _,
+                core::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            Self { buffer, start: Instant::now() }
+        }
+    }
+
+    /// Writes `data` and binds the buffer to `FRAME_BINDING`.
+    pub fn upload(&self, data: &FrameData) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffer);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                std::mem::size_of::<FrameData>() as _,
+                data as *const FrameData as *const _,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, FRAME_BINDING, self.buffer);
+        }
+    }
+}
+
+impl Default for FrameUniforms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FrameUniforms {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
+
+/// Fills this thread's frame buffer for a frame seen through `camera`, at the current viewport.
+/// Call once per frame before drawing.
+pub fn begin(camera: &Camera) {
+    let viewport = Viewport::current();
+    FRAME_UNIFORMS.with(|uniforms| {
+        let mut uniforms = uniforms.borrow_mut();
+        let uniforms = uniforms.get_or_insert_with(FrameUniforms::new);
+        let time = uniforms.start.elapsed().as_secs_f32();
+        uniforms.upload(&FrameData::new(camera, time, (viewport.width as f32, viewport.height as f32)));
+    });
+}
+
+/// Deletes this thread's frame buffer, before the context it was created in goes away.
+pub(crate) fn reset() {
+    FRAME_UNIFORMS.with(|uniforms| uniforms.borrow_mut().take());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::{offset_of, size_of};
+
+    #[test]
+    fn layout_matches_std140() {
+        assert_eq!(offset_of!(FrameData, view), 0);
+        assert_eq!(offset_of!(FrameData, projection), 64);
+        assert_eq!(offset_of!(FrameData, camera_position), 128);
+        assert_eq!(offset_of!(FrameData, time), 140);
+        assert_eq!(offset_of!(FrameData, viewport), 144);
+        assert_eq!(size_of::<FrameData>() % 16, 0);
+    }
+}
@@ -0,0 +1,202 @@
+//! Golden-image tests: canned scenes rendered headless and compared with the references in
+//! `tests/golden`. After an intended change in the output, run the tests with `UPDATE_GOLDEN=1`
+//! to replace the references, and review the new images before committing them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::headless::test_context;
+use crate::image::Image;
+use crate::light::Light;
+use crate::math::{Camera, Degrees, Vec3};
+use crate::objects::{Plane, Sphere};
+use crate::scene::Scene;
+use crate::state::{Blend, Cull, RenderOverrides};
+use crate::traits::*;
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 144;
+
+/// Perceived difference above which two pixels count as different, from 0 for the same color to
+/// 1 for black against white.
+const PIXEL_THRESHOLD: f32 = 0.1;
+/// Share of pixels that may differ, for anti-aliasing and rounding that vary between Mesa versions.
+const MAX_DIFFERENT: f32 = 0.002;
+
+struct Comparison {
+    different: usize,
+    max_difference: f32,
+    /// The reference faded to gray, with different pixels in red and slightly different ones in yellow.
+    diff: Image,
+}
+
+/// Distance between two colors in YIQ space, which weighs brightness over hue like the eye does.
+/// Alpha is ignored since the references are stored without it.
+fn difference(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let yiq = |[r, g, b, _]: [u8; 4]| {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        (
+            0.298_895_3 * r + 0.586_622_5 * g + 0.114_482_2 * b,
+            0.595_977_99 * r - 0.274_176_1 * g - 0.321_801_9 * b,
+            0.211_470_17 * r - 0.522_617_1 * g + 0.311_146_94 * b,
+        )
+    };
+    let ((y1, i1, q1), (y2, i2, q2)) = (yiq(a), yiq(b));
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+    // Scaled so black against white is 1, where only brightness differs.
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / (0.5053 * 255.0 * 255.0)).sqrt()
+}
+
+fn compare(actual: &Image, expected: &Image) -> Comparison {
+    let mut comparison = Comparison { different: 0, max_difference: 0.0, diff: Image::new(expected.width(), expected.height()) };
+    for y in 0..expected.height() {
+        for x in 0..expected.width() {
+            let (a, b) = (actual.pixel(x, y), expected.pixel(x, y));
+            let d = difference(a, b);
+            comparison.max_difference = comparison.max_difference.max(d);
+            let [r, g, b, _] = b;
+            let gray = (255.0 - 0.1 * (255.0 - (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32))) as u8;
+            let color = if d > PIXEL_THRESHOLD {
+                comparison.different += 1;
+                [255, 0, 0, 255]
+            } else if d > 0.0 {
+                [255, 255, 0, 255]
+            } else {
+                [gray, gray, gray, 255]
+            };
+            comparison.diff.set_pixel(x, y, color);
+        }
+    }
+    comparison
+}
+
+/// Where failing tests write the rendered image, the reference and their difference: `golden` in
+/// the target directory, which `CARGO_TARGET_DIR` may move.
+fn output_dir() -> PathBuf {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    match std::env::var_os("CARGO_TARGET_DIR") {
+        // A relative target directory is relative to where cargo ran, which may be anywhere
+        // under the workspace; the manifest directory is the best guess.
+        Some(target) => manifest.join(target).join("golden"),
+        None => manifest.join("target/golden"),
+    }
+}
+
+/// Compares `actual` with the reference called `name`. On a mismatch the images are written to
+/// `output_dir()` and the error says where.
+fn check(name: &str, actual: &Image) -> Result<(), String> {
+    let reference = Path::new(GOLDEN_DIR).join(format!("{}.ppm", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(GOLDEN_DIR).map_err(|e| e.to_string())?;
+        return actual.save(&reference).map_err(|e| format!("Couldn't write {}: {}", reference.display(), e));
+    }
+
+    let output = output_dir();
+    fs::create_dir_all(&output).map_err(|e| e.to_string())?;
+    let save = |suffix: &str, image: &Image| {
+        let path = output.join(format!("{}.{}.png", name, suffix));
+        image.save(&path).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    };
+    let expected = match Image::load_ppm(&reference) {
+        Ok(expected) => expected,
+        Err(e) => {
+            save("actual", actual)?;
+            return Err(format!("{}: couldn't read {} ({}). Run with UPDATE_GOLDEN=1 to create it.", name, reference.display(), e));
+        }
+    };
+    if (actual.width(), actual.height()) != (expected.width(), expected.height()) {
+        save("actual", actual)?;
+        return Err(format!("{}: rendered {}x{}, the reference is {}x{}.", name, actual.width(), actual.height(), expected.width(), expected.height()));
+    }
+
+    let comparison = compare(actual, &expected);
+    let share = comparison.different as f32 / (expected.width() * expected.height()) as f32;
+    if share > MAX_DIFFERENT {
+        save("actual", actual)?;
+        save("expected", &expected)?;
+        save("diff", &comparison.diff)?;
+        return Err(format!(
+            "{}: {} pixels ({:.2}%) differ from the reference, up to {:.2}. See {}.",
+            name, comparison.different, share * 100.0, comparison.max_difference, output.join(format!("{}.diff.png", name)).display(),
+        ));
+    }
+    Ok(())
+}
+
+/// The camera of `main`.
+fn camera() -> Camera {
+    let mut camera = Camera::new(WIDTH as f32 / HEIGHT as f32, Degrees(70.0), 1.0, 100.0);
+    camera.look_at(Vec3(0.0, 0.0, 10.0), Vec3::zero(), Vec3::up());
+    camera
+}
+
+fn assert_all(results: Vec<Result<(), String>>) {
+    let errors: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+}
+
+#[test]
+fn spinning_spheres() {
+    let headless = test_context(WIDTH, HEIGHT);
+    let mut results = Vec::new();
+    for angle in [0.0, 30.0, 90.0] {
+        headless.next_frame();
+        let (mut scene, spheres) = crate::demo::build_scene().unwrap();
+        crate::demo::spin(&mut scene, spheres, angle);
+        scene.draw(&camera());
+        results.push(check(&format!("spheres_{}", angle), &headless.capture_frame()));
+    }
+    assert_all(results);
+}
+
+#[test]
+fn render_overrides() {
+    let headless = test_context(WIDTH, HEIGHT);
+    headless.next_frame();
+    let mut scene = Scene::new();
+    scene.add_light(Light::directional(Vec3(-1.0, -1.0, -1.0), Vec3(1.0, 1.0, 1.0), 0.8));
+
+    let mut ground = Plane::new().unwrap();
+    ground.scaled_by(15.0);
+    ground.set_position(Vec3(0.0, -4.0, 0.0));
+    ground.set_color(200, 200, 200);
+    scene.add("ground", ground);
+
+    // Only the inside of the far half shows.
+    let mut inside = Sphere::new(6).unwrap();
+    inside.scaled_by(2.0);
+    inside.set_position(Vec3(-4.0, 0.0, 0.0));
+    inside.set_render_overrides(RenderOverrides { cull: Some(Cull::Front), ..RenderOverrides::default() });
+    scene.add("inside", inside);
+
+    // Drawn last so the ground shows through.
+    let mut glass = Sphere::new(6).unwrap();
+    glass.scaled_by(2.0);
+    glass.set_position(Vec3(4.0, -2.0, 0.0));
+    glass.set_alpha(128);
+    glass.set_render_overrides(RenderOverrides { blend: Some(Blend::Alpha), depth_write: Some(false), ..RenderOverrides::default() });
+    scene.add("glass", glass);
+
+    scene.draw(&camera());
+    assert_all(vec![check("render_overrides", &headless.capture_frame())]);
+}
+
+#[test]
+fn comparison_tolerates_small_differences() {
+    let mut expected = Image::new(4, 4);
+    for y in 0..4 {
+        for x in 0..4 {
+            expected.set_pixel(x, y, [100, 150, 200, 255]);
+        }
+    }
+    let mut actual = expected.clone();
+    actual.set_pixel(0, 0, [102, 151, 199, 255]);
+    actual.set_pixel(3, 3, [255, 0, 0, 255]);
+
+    let comparison = compare(&actual, &expected);
+    assert_eq!(comparison.different, 1);
+    assert_eq!(comparison.diff.pixel(3, 3), [255, 0, 0, 255]);
+    assert_eq!(comparison.diff.pixel(0, 0), [255, 255, 0, 255]);
+    assert!(difference([0, 0, 0, 255], [255, 255, 255, 255]) > 0.99);
+    assert_eq!(difference([1, 2, 3, 0], [1, 2, 3, 255]), 0.0);
+}
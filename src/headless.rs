@@ -0,0 +1,340 @@
+use std::cell::Cell;
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::OnceLock;
+use crate::capture;
+use crate::context::{self, GlConfig, GlProfile};
+use crate::frame;
+use crate::gl;
+use crate::image::Image;
+use crate::state::{ClearState, RenderState, Viewport};
+
+type EGLDisplay = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLSurface = *mut c_void;
+type EGLBoolean = u32;
+type EGLint = i32;
+
+type GetError = unsafe extern "C" fn() -> EGLint;
+type GetProcAddress = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type QueryString = unsafe extern "C" fn(EGLDisplay, EGLint) -> *const c_char;
+type Initialize = unsafe extern "C" fn(EGLDisplay, *mut EGLint, *mut EGLint) -> EGLBoolean;
+type BindApi = unsafe extern "C" fn(u32) -> EGLBoolean;
+type CreateContext = unsafe extern "C" fn(EGLDisplay, EGLConfig, EGLContext, *const EGLint) -> EGLContext;
+type DestroyContext = unsafe extern "C" fn(EGLDisplay, EGLContext) -> EGLBoolean;
+type MakeCurrent = unsafe extern "C" fn(EGLDisplay, EGLSurface, EGLSurface, EGLContext) -> EGLBoolean;
+type GetPlatformDisplay = unsafe extern "C" fn(u32, *mut c_void, *const EGLint) -> EGLDisplay;
+
+const EGL_FALSE: EGLBoolean = 0;
+const EGL_NONE: EGLint = 0x3038;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_OPENGL_API: u32 = 0x30a2;
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30fb;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30fd;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x1;
+const EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT: EGLint = 0x2;
+const EGL_CONTEXT_OPENGL_DEBUG: EGLint = 0x31b0;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31dd;
+
+/// The EGL entry points. libEGL is loaded the first time a headless context is created rather
+/// than linked, so the windowed binary runs on systems without it.
+struct Egl {
+    get_error: GetError,
+    get_proc_address: GetProcAddress,
+    query_string: QueryString,
+    initialize: Initialize,
+    bind_api: BindApi,
+    create_context: CreateContext,
+    destroy_context: DestroyContext,
+    make_current: MakeCurrent,
+}
+
+static EGL: OnceLock<Result<Egl, String>> = OnceLock::new();
+
+impl Egl {
+    fn get() -> Result<&'static Egl, String> {
+        EGL.get_or_init(|| unsafe { Egl::load() }).as_ref().map_err(Clone::clone)
+    }
+
+    /// Never unloaded, since contexts may live until the process exits.
+    unsafe fn load() -> Result<Self, String> {
+        let library = [c"libEGL.so.1", c"libEGL.so"].iter()
+            .map(|name| libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL))
+            .find(|library| !library.is_null())
+            .ok_or_else(|| "Couldn't create a headless context: libEGL.so.1 isn't installed.".to_string())?;
+        let symbol = |name: &CStr| {
+            let symbol = libc::dlsym(library, name.as_ptr());
+            if symbol.is_null() {
+                Err(format!("Couldn't create a headless context: libEGL has no {}.", name.to_string_lossy()))
+            } else {
+                Ok(symbol)
+            }
+        };
+        Ok(Self {
+            get_error: std::mem::transmute::<*mut c_void, GetError>(symbol(c"eglGetError")?),
+            get_proc_address: std::mem::transmute::<*mut c_void, GetProcAddress>(symbol(c"eglGetProcAddress")?),
+            query_string: std::mem::transmute::<*mut c_void, QueryString>(symbol(c"eglQueryString")?),
+            initialize: std::mem::transmute::<*mut c_void, Initialize>(symbol(c"eglInitialize")?),
+            bind_api: std::mem::transmute::<*mut c_void, BindApi>(symbol(c"eglBindAPI")?),
+            create_context: std::mem::transmute::<*mut c_void, CreateContext>(symbol(c"eglCreateContext")?),
+            destroy_context: std::mem::transmute::<*mut c_void, DestroyContext>(symbol(c"eglDestroyContext")?),
+            make_current: std::mem::transmute::<*mut c_void, MakeCurrent>(symbol(c"eglMakeCurrent")?),
+        })
+    }
+}
+
+/// An OpenGL context without a window or display server, through EGL's surfaceless platform.
+/// Mesa provides it on every driver, including llvmpipe.
+struct EglContext {
+    egl: &'static Egl,
+    display: EGLDisplay,
+    context: EGLContext,
+}
+
+impl EglContext {
+    unsafe fn new(config: &GlConfig) -> Result<Self, String> {
+        let egl = Egl::get()?;
+        let extensions = (egl.query_string)(core::ptr::null_mut(), EGL_EXTENSIONS);
+        if extensions.is_null() || !CStr::from_ptr(extensions).to_string_lossy().split_whitespace().any(|e| e == "EGL_MESA_platform_surfaceless") {
+            return Err("Couldn't create a headless context: EGL_MESA_platform_surfaceless isn't supported.".to_string());
+        }
+        let get_display = (egl.get_proc_address)(c"eglGetPlatformDisplayEXT".as_ptr());
+        if get_display.is_null() {
+            return Err("Couldn't create a headless context: eglGetPlatformDisplayEXT is missing.".to_string());
+        }
+        let get_display = std::mem::transmute::<*mut c_void, GetPlatformDisplay>(get_display);
+        let display = get_display(EGL_PLATFORM_SURFACELESS_MESA, core::ptr::null_mut(), core::ptr::null());
+        // Initializing an initialized display does nothing, so every context can do it. The
+        // display is never terminated, which would destroy the contexts of other threads.
+        if display.is_null() || (egl.initialize)(display, core::ptr::null_mut(), core::ptr::null_mut()) == EGL_FALSE {
+            return Err(format!("Couldn't initialize the EGL display (error {:#x}).", (egl.get_error)()));
+        }
+        if (egl.bind_api)(EGL_OPENGL_API) == EGL_FALSE {
+            return Err("Couldn't create a headless context: EGL doesn't support desktop OpenGL.".to_string());
+        }
+
+        let profile = match config.profile {
+            GlProfile::Core => EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            GlProfile::Compatibility => EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT,
+        };
+        let context_attributes = [
+            EGL_CONTEXT_MAJOR_VERSION, config.version.0 as EGLint,
+            EGL_CONTEXT_MINOR_VERSION, config.version.1 as EGLint,
+            EGL_CONTEXT_OPENGL_PROFILE_MASK, profile,
+            EGL_CONTEXT_OPENGL_DEBUG, config.debug as EGLint,
+            EGL_NONE,
+        ];
+        // Without a surface there is no need for a config (EGL_KHR_no_config_context).
+        let context = (egl.create_context)(display, core::ptr::null_mut(), core::ptr::null_mut(), context_attributes.as_ptr());
+        if context.is_null() {
+            return Err(format!("Couldn't create an {} headless context (error {:#x}).", config, (egl.get_error)()));
+        }
+        if (egl.make_current)(display, core::ptr::null_mut(), core::ptr::null_mut(), context) == EGL_FALSE {
+            (egl.destroy_context)(display, context);
+            return Err(format!("Couldn't make the {} headless context current.", config));
+        }
+        Ok(Self { egl, display, context })
+    }
+}
+
+impl Drop for EglContext {
+    fn drop(&mut self) {
+        unsafe {
+            // The frame uniforms belong to this context; the next one on this thread makes its own.
+            frame::reset();
+            (self.egl.make_current)(self.display, core::ptr::null_mut(), core::ptr::null_mut(), core::ptr::null_mut());
+            (self.egl.destroy_context)(self.display, self.context);
+        }
+    }
+}
+
+/// A framebuffer object to render into instead of a window, with RGBA8 color and 24-bit depth
+/// and 8-bit stencil. With MSAA it renders into multisampled buffers and resolves them when read.
+pub struct RenderTarget {
+    width: u32,
+    height: u32,
+    framebuffer: u32,
+    renderbuffers: [u32; 2],
+    resolve: Option<(u32, u32)>,
+}
+
+impl RenderTarget {
+    /// Uses the samples and sRGB setting of `config`. Needs a current context.
+    pub fn new(width: u32, height: u32, config: &GlConfig) -> Result<Self, String> {
+        let format = if config.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+        unsafe {
+            let (framebuffer, renderbuffers) = create_framebuffer(width, height, config.samples, &[format, gl::DEPTH24_STENCIL8]);
+            let mut target = Self { width, height, framebuffer, renderbuffers: [renderbuffers[0], renderbuffers[1]], resolve: None };
+            if config.samples > 0 {
+                let (framebuffer, renderbuffers) = create_framebuffer(width, height, 0, &[format]);
+                target.resolve = Some((framebuffer, renderbuffers[0]));
+            }
+            for framebuffer in [Some(target.framebuffer), target.resolve.map(|(resolve, _)| resolve)].into_iter().flatten() {
+                let status = gl::CheckNamedFramebufferStatus(framebuffer, gl::FRAMEBUFFER);
+                if status != gl::FRAMEBUFFER_COMPLETE {
+                    return Err(format!("Couldn't create a {}x{} render target (status {:#x}).", width, height, status));
+                }
+            }
+            Ok(target)
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    /// Directs drawing into this target and sets the viewport to cover it.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+        Viewport::new(self.width as _, self.height as _).apply();
+    }
+
+    /// Waits for drawing to finish and reads the color buffer.
+    pub fn read_pixels(&self) -> Image {
+        unsafe {
+            let mut previous = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+            let source = match self.resolve {
+                Some((resolve, _)) => {
+                    let (width, height) = (self.width as i32, self.height as i32);
+                    gl::BlitNamedFramebuffer(self.framebuffer, resolve, 0, 0, width, height, 0, 0, width, height, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+                    resolve
+                }
+                None => self.framebuffer,
+            };
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source);
+            let image = capture::read_pixels(self.width, self.height);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as u32);
+            image
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteRenderbuffers(2, self.renderbuffers.as_ptr());
+            if let Some((framebuffer, renderbuffer)) = self.resolve {
+                gl::DeleteFramebuffers(1, &framebuffer);
+                gl::DeleteRenderbuffers(1, &renderbuffer);
+            }
+        }
+    }
+}
+
+/// A framebuffer with one renderbuffer per format, color first and depth-stencil last.
+unsafe fn create_framebuffer(width: u32, height: u32, samples: u32, formats: &[gl::types::GLenum]) -> (u32, Vec<u32>) {
+    let mut framebuffer = 0;
+    gl::CreateFramebuffers(1, &mut framebuffer);
+    let mut renderbuffers = vec![0; formats.len()];
+    gl::CreateRenderbuffers(formats.len() as _, renderbuffers.as_mut_ptr());
+    for (&renderbuffer, &format) in renderbuffers.iter().zip(formats) {
+        gl::NamedRenderbufferStorageMultisample(renderbuffer, samples as _, format, width as _, height as _);
+        let attachment = if format == gl::DEPTH24_STENCIL8 { gl::DEPTH_STENCIL_ATTACHMENT } else { gl::COLOR_ATTACHMENT0 };
+        gl::NamedFramebufferRenderbuffer(framebuffer, attachment, gl::RENDERBUFFER, renderbuffer);
+    }
+    (framebuffer, renderbuffers)
+}
+
+/// Renders without a display: a surfaceless EGL context drawing into a `RenderTarget`, for CI
+/// and batch jobs. Draw the same `Drawable`s and `Scene`s as with a `Window`, then read the
+/// frame back with `capture_frame`.
+pub struct Headless {
+    // Dropped before the context it was created in.
+    target: RenderTarget,
+    // Only held to keep the context alive.
+    _context: EglContext,
+    clear: Cell<ClearState>,
+}
+
+impl Headless {
+    /// Creates the context, makes it current on this thread and binds a `width` by `height`
+    /// target. Use one `Headless` per thread.
+    pub fn new(width: u32, height: u32, config: &GlConfig) -> Result<Self, String> {
+        unsafe {
+            let context = EglContext::new(config)?;
+            context::apply(config);
+            let target = RenderTarget::new(width, height, config)?;
+            let headless = Self { target, _context: context, clear: Cell::new(ClearState::default()) };
+            headless.target.bind();
+            RenderState::default().apply();
+            headless.clear_state().clear();
+            Ok(headless)
+        }
+    }
+
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.target.size()
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.target.aspect()
+    }
+
+    /// Reads back the frame drawn so far.
+    pub fn capture_frame(&self) -> Image {
+        self.target.read_pixels()
+    }
+
+    /// Clears the buffers for the next frame, like `Window::swap_buffers`.
+    pub fn next_frame(&self) {
+        self.target.bind();
+        self.clear_state().clear();
+    }
+
+    pub fn clear_state(&self) -> ClearState {
+        self.clear.get()
+    }
+
+    /// Values `next_frame` clears the color, depth and stencil buffers to.
+    pub fn set_clear_state(&self, clear: ClearState) {
+        self.clear.set(clear);
+    }
+}
+
+/// A single-sampled `Headless` for tests, which fail rather than pass unchecked without OpenGL.
+#[cfg(test)]
+pub fn test_context(width: u32, height: u32) -> Headless {
+    Headless::new(width, height, &GlConfig::default()).unwrap_or_else(|e| panic!("No headless OpenGL: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Camera, Degrees, Vec3};
+    use crate::objects::Plane;
+    use crate::traits::*;
+
+    #[test]
+    fn renders_offscreen() {
+        // Multisampled, unlike `test_context`, to cover the resolve.
+        let headless = Headless::new(64, 32, &GlConfig { samples: 4, ..GlConfig::default() }).expect("Couldn't create a headless context");
+        headless.set_clear_state(ClearState { color: [0.0, 0.0, 1.0, 1.0], ..ClearState::default() });
+        headless.next_frame();
+
+        // A plane facing the camera, filling the middle of the frame.
+        let mut camera = Camera::new(headless.aspect(), Degrees(90.0), 0.1, 10.0);
+        camera.look_at(Vec3(0.0, 5.0, 0.0), Vec3::zero(), Vec3::front());
+        let mut plane = Plane::new().unwrap();
+        plane.set_color(255, 0, 0);
+        plane.draw(&camera);
+
+        let image = headless.capture_frame();
+        assert_eq!((image.width(), image.height()), (64, 32));
+        assert_eq!(image.pixel(0, 0), [0, 0, 255, 255]);
+        let [red, green, blue, _] = image.pixel(32, 16);
+        assert!(red > 0 && green == 0 && blue == 0, "{:?}", image.pixel(32, 16));
+    }
+}
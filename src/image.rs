@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+/// 8-bit RGBA pixels, rows from top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = byte_len(width, height, 4).expect("Image is too large.");
+        Self { width, height, pixels: vec![0; size] }
+    }
+
+    /// `pixels` must hold `width * height` RGBA pixels.
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(Some(pixels.len()), byte_len(width, height, 4), "Pixel data doesn't match the image size.");
+        Self { width, height, pixels }
+    }
+
+    /// From rows ordered bottom to top, as `glReadPixels` returns them.
+    pub fn from_gl_rgba(width: u32, height: u32, pixels: &[u8]) -> Self {
+        let row = width as usize * 4;
+        let pixels = pixels.chunks_exact(row.max(1)).rev().flatten().copied().collect();
+        Self::from_rgba(width, height, pixels)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..]
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    /// Pixels without alpha, for formats and tools that only take RGB.
+    pub fn rgb(&self) -> Vec<u8> {
+        self.pixels.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect()
+    }
+
+    /// Writes an uncompressed PNG for a `.png` extension, see `write_png`, or a binary PPM without
+    /// alpha for `.ppm`. Other extensions are rejected.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        let png = match extension.as_deref() {
+            Some("png") => true,
+            Some("ppm") => false,
+            _ => {
+                let message = format!("Can't save {}: only .png and .ppm are supported.", path.display());
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        };
+        let mut file = BufWriter::new(File::create(path)?);
+        if png {
+            self.write_png(&mut file)?;
+        } else {
+            self.write_ppm(&mut file)?;
+        }
+        file.flush()
+    }
+
+    pub fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.rgb())
+    }
+
+    /// Reads a binary (P6) PPM with 8-bit samples. Alpha is set to opaque.
+    pub fn read_ppm(input: &mut impl Read) -> io::Result<Image> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+
+        // Header fields are separated by whitespace, with `#` comments, and end after one
+        // whitespace byte following the maximum value.
+        let mut fields = Vec::new();
+        let mut i = 0;
+        while fields.len() < 4 {
+            match data.get(i) {
+                None => return Err(invalid("Truncated PPM header.")),
+                Some(b'#') => while data.get(i).is_some_and(|&b| b != b'\n') { i += 1 },
+                Some(b) if b.is_ascii_whitespace() => i += 1,
+                Some(_) => {
+                    let start = i;
+                    while data.get(i).is_some_and(|b| !b.is_ascii_whitespace()) { i += 1 }
+                    fields.push(String::from_utf8_lossy(&data[start..i]).into_owned());
+                }
+            }
+        }
+        if fields[0] != "P6" {
+            return Err(invalid("Only binary PPM (P6) is supported."));
+        }
+        let number = |s: &str| s.parse::<u32>().map_err(|_| invalid("Malformed PPM header."));
+        let (width, height, max) = (number(&fields[1])?, number(&fields[2])?, number(&fields[3])?);
+        if max != 255 {
+            return Err(invalid("Only 8-bit PPM is supported."));
+        }
+        let size = byte_len(width, height, 3).ok_or_else(|| invalid("PPM image is too large."))?;
+        let rgb = data.get(i + 1..).and_then(|rgb| rgb.get(..size)).ok_or_else(|| invalid("Truncated PPM data."))?;
+        let pixels = rgb.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect();
+        Ok(Image::from_rgba(width, height, pixels))
+    }
+
+    pub fn load_ppm(path: impl AsRef<Path>) -> io::Result<Image> {
+        Image::read_ppm(&mut File::open(path)?)
+    }
+
+    /// Writes an RGBA PNG. Rows aren't filtered and the data goes into stored deflate blocks, so
+    /// the file is as large as the raw pixels: quick to write, but meant for screenshots and test
+    /// output rather than distribution.
+    pub fn write_png(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::new();
+        header.extend(self.width.to_be_bytes());
+        header.extend(self.height.to_be_bytes());
+        // 8 bits per sample, RGBA, compression method 0 (deflate), filter method 0, no interlace.
+        header.extend([8, 6, 0, 0, 0]);
+        write_chunk(out, b"IHDR", &header)?;
+
+        // Every row starts with its filter type, always 0 for none.
+        let row = self.width as usize * 4;
+        let mut raw = Vec::with_capacity((row + 1) * self.height as usize);
+        for line in self.pixels.chunks_exact(row.max(1)) {
+            raw.push(0);
+            raw.extend_from_slice(line);
+        }
+        write_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+        write_chunk(out, b"IEND", &[])
+    }
+}
+
+/// Bytes taken by `width * height` pixels of `channels` bytes, `None` if that overflows.
+fn byte_len(width: u32, height: u32, channels: usize) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(channels)
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())
+}
+
+/// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        out.push(last as u8);
+        out.extend(length.to_le_bytes());
+        out.extend((!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32<'a>(data: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient() -> Image {
+        let mut image = Image::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                image.set_pixel(x, y, [x as u8 * 100, y as u8 * 200, 7, 255]);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn png_layout() {
+        let mut png = Vec::new();
+        gradient().write_png(&mut png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+        assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+        // Two rows of a filter byte and 3 pixels, stored in one block after the zlib header.
+        let idat = 8 + 25;
+        assert_eq!(&png[idat + 4..idat + 8], b"IDAT");
+        assert_eq!(&png[idat + 8..idat + 15], &[0x78, 0x01, 1, 26, 0, !26, 0xff]);
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let image = gradient();
+        let mut ppm = Vec::new();
+        image.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n3 2\n255\n"));
+        assert_eq!(Image::read_ppm(&mut &ppm[..]).unwrap(), image);
+
+        let commented = b"P6 # comment\n1 1\n255\n\x01\x02\x03";
+        assert_eq!(Image::read_ppm(&mut &commented[..]).unwrap().pixel(0, 0), [1, 2, 3, 255]);
+        assert!(Image::read_ppm(&mut &b"P6\n2 2\n255\n\x00"[..]).is_err());
+
+        // Sizes too large for memory are errors, not overflows.
+        for header in [&b"P6\n4294967295 4294967295\n255\n"[..], &b"P6\n65536 65536\n255\n\x00"[..]] {
+            let error = Image::read_ppm(&mut &header[..]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn save_picks_the_format_from_the_extension() {
+        let directory = std::env::temp_dir().join(format!("image-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let image = gradient();
+        image.save(directory.join("a.PPM")).unwrap();
+        assert_eq!(Image::load_ppm(directory.join("a.PPM")).unwrap(), image);
+        image.save(directory.join("a.png")).unwrap();
+        assert!(std::fs::read(directory.join("a.png")).unwrap().starts_with(b"\x89PNG"));
+        for name in ["a.jpg", "a.pmm", "a"] {
+            assert_eq!(image.save(directory.join(name)).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+            assert!(!directory.join(name).exists());
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn gl_rows_are_flipped() {
+        let bottom_up = [1, 1, 1, 1, 2, 2, 2, 2];
+        let image = Image::from_gl_rgba(1, 2, &bottom_up);
+        assert_eq!(image.pixel(0, 0), [2, 2, 2, 2]);
+        assert_eq!(image.pixel(0, 1), [1, 1, 1, 1]);
+        assert_eq!(Image::from_gl_rgba(0, 2, &[]).height(), 2);
+    }
+}
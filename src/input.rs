@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+/// An X11 keysym, e.g. `x11::keysym::XK_Escape`. Letters are reported lowercase.
+pub type KeySym = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Other(u32),
+}
+
+impl MouseButton {
+    pub(crate) fn from_x11(button: u32) -> Self {
+        match button {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            _ => MouseButton::Other(button),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// `repeat` is set for auto-repeat while the key is held.
+    KeyDown { keysym: KeySym, repeat: bool },
+    KeyUp { keysym: KeySym },
+    /// Pointer position in pixels from the top-left corner of the window.
+    MouseMove { x: f32, y: f32 },
+    MouseButton { button: MouseButton, pressed: bool },
+    /// Wheel steps; positive `y` scrolls up, positive `x` right.
+    Scroll { x: f32, y: f32 },
+    Resize { width: u32, height: u32 },
+    Focus(bool),
+    Close,
+}
+
+/// Keys and buttons held, plus what changed during the current frame.
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    keys: HashSet<KeySym>,
+    pressed: HashSet<KeySym>,
+    released: HashSet<KeySym>,
+    buttons: HashSet<MouseButton>,
+    mouse: Option<(f32, f32)>,
+    mouse_delta: (f32, f32),
+    scroll: (f32, f32),
+    focused: bool,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets per-frame changes, keeping what is held.
+    pub fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll = (0.0, 0.0);
+    }
+
+    pub fn handle(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown { keysym, repeat } => {
+                if !repeat && self.keys.insert(keysym) {
+                    self.pressed.insert(keysym);
+                }
+            }
+            Event::KeyUp { keysym } => {
+                if self.keys.remove(&keysym) {
+                    self.released.insert(keysym);
+                }
+            }
+            Event::MouseMove { x, y } => {
+                if let Some((old_x, old_y)) = self.mouse {
+                    self.mouse_delta.0 += x - old_x;
+                    self.mouse_delta.1 += y - old_y;
+                }
+                self.mouse = Some((x, y));
+            }
+            Event::MouseButton { button, pressed: true } => {
+                self.buttons.insert(button);
+            }
+            Event::MouseButton { button, pressed: false } => {
+                self.buttons.remove(&button);
+            }
+            Event::Scroll { x, y } => {
+                self.scroll.0 += x;
+                self.scroll.1 += y;
+            }
+            Event::Focus(focused) => {
+                self.focused = focused;
+                // Releases that happen while unfocused never reach us.
+                if !focused {
+                    self.keys.clear();
+                    self.buttons.clear();
+                }
+            }
+            Event::Resize { .. } | Event::Close => {}
+        }
+    }
+
+    pub fn is_key_down(&self, keysym: KeySym) -> bool {
+        self.keys.contains(&keysym)
+    }
+
+    /// Whether `keysym` went down this frame.
+    pub fn was_key_pressed(&self, keysym: KeySym) -> bool {
+        self.pressed.contains(&keysym)
+    }
+
+    /// Whether `keysym` went up this frame.
+    pub fn was_key_released(&self, keysym: KeySym) -> bool {
+        self.released.contains(&keysym)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    /// Last known pointer position, `None` until the pointer has moved over the window.
+    pub fn mouse_position(&self) -> Option<(f32, f32)> {
+        self.mouse
+    }
+
+    /// Pointer movement this frame.
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    /// Wheel steps this frame.
+    pub fn scroll(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11::keysym::{XK_a, XK_w};
+
+    #[test]
+    fn keys_are_held_until_released() {
+        let mut input = InputState::new();
+        input.handle(&Event::KeyDown { keysym: XK_w, repeat: false });
+        assert!(input.is_key_down(XK_w));
+        assert!(input.was_key_pressed(XK_w));
+
+        input.begin_frame();
+        input.handle(&Event::KeyDown { keysym: XK_w, repeat: true });
+        assert!(input.is_key_down(XK_w));
+        assert!(!input.was_key_pressed(XK_w));
+
+        input.handle(&Event::KeyUp { keysym: XK_w });
+        assert!(!input.is_key_down(XK_w));
+        assert!(input.was_key_released(XK_w));
+        assert!(!input.is_key_down(XK_a));
+    }
+
+    #[test]
+    fn mouse_motion_and_scroll_accumulate_per_frame() {
+        let mut input = InputState::new();
+        input.handle(&Event::MouseMove { x: 10.0, y: 10.0 });
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+        input.handle(&Event::MouseMove { x: 13.0, y: 8.0 });
+        input.handle(&Event::MouseMove { x: 15.0, y: 9.0 });
+        input.handle(&Event::Scroll { x: 0.0, y: 1.0 });
+        input.handle(&Event::Scroll { x: 0.0, y: 1.0 });
+        assert_eq!(input.mouse_delta(), (5.0, -1.0));
+        assert_eq!(input.scroll(), (0.0, 2.0));
+
+        input.begin_frame();
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+        assert_eq!(input.scroll(), (0.0, 0.0));
+        assert_eq!(input.mouse_position(), Some((15.0, 9.0)));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything() {
+        let mut input = InputState::new();
+        input.handle(&Event::Focus(true));
+        input.handle(&Event::KeyDown { keysym: XK_a, repeat: false });
+        input.handle(&Event::MouseButton { button: MouseButton::Left, pressed: true });
+        assert!(input.is_button_down(MouseButton::Left));
+        input.handle(&Event::Focus(false));
+        assert!(!input.is_key_down(XK_a));
+        assert!(!input.is_button_down(MouseButton::Left));
+        assert!(!input.is_focused());
+    }
+}
@@ -0,0 +1,28 @@
+pub mod capture;
+pub mod clock;
+pub mod context;
+pub mod demo;
+pub mod frame;
+#[cfg(test)]
+mod golden;
+pub mod headless;
+pub mod image;
+pub mod input;
+pub mod light;
+pub mod math;
+pub mod mesh;
+pub mod objects;
+pub mod preprocess;
+pub mod scene;
+pub mod shader;
+pub mod shadow;
+pub mod state;
+pub mod traits;
+pub mod uniform;
+pub mod watch;
+pub mod window;
+
+#[allow(clippy::all, unused_imports)]
+pub mod gl {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
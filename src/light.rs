@@ -0,0 +1,104 @@
+use crate::math::{Degrees, Radians, Vec3};
+use crate::shader::Program;
+use crate::shadow::ShadowMap;
+use crate::traits::Positioned;
+
+/// Number of lights the built-in shaders evaluate, matching `MAX_LIGHTS` in `common.frag`.
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    Point,
+    Directional,
+    /// Full intensity inside `inner`, fading out towards `outer`. Both are half-angles.
+    Spot { inner: Degrees, outer: Degrees },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Only directional and spot lights can cast shadows.
+    pub casts_shadows: bool,
+}
+
+/// Lights and shadow maps an object is drawn with, in addition to its own light sources.
+#[derive(Clone, Copy, Default)]
+pub struct Lighting<'a> {
+    pub lights: &'a [Light],
+    pub shadow_maps: &'a [ShadowMap],
+}
+
+impl Light {
+    pub fn point(position: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position,
+            direction: Vec3::back(),
+            color,
+            intensity,
+            casts_shadows: false,
+        }
+    }
+
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            position: Vec3::zero(),
+            direction: direction.normalized(),
+            color,
+            intensity,
+            casts_shadows: false,
+        }
+    }
+
+    pub fn spot(position: Vec3, direction: Vec3, inner: Degrees, outer: Degrees, color: Vec3, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Spot { inner, outer },
+            position,
+            direction: direction.normalized(),
+            color,
+            intensity,
+            casts_shadows: false,
+        }
+    }
+
+    pub fn with_shadows(mut self) -> Self {
+        self.casts_shadows = true;
+        self
+    }
+
+    /// Sets the `lights` and `lightCount` uniforms of `program`. Lights past `MAX_LIGHTS` are
+    /// ignored. Shadow maps refer to lights by their index in `lights`.
+    pub(crate) fn upload<'a>(program: &Program, lights: impl Iterator<Item = &'a Light>, shadow_maps: &[ShadowMap]) {
+        let mut count = 0;
+        for (i, light) in lights.take(MAX_LIGHTS).enumerate() {
+            let (kind, inner, outer) = match light.kind {
+                LightKind::Point => (0, 0.0, 0.0),
+                LightKind::Directional => (1, 0.0, 0.0),
+                LightKind::Spot { inner, outer } => (2, Radians::from(inner).0.cos(), Radians::from(outer).0.cos()),
+            };
+            let field = |name: &str| format!("lights[{}].{}", i, name);
+            program.set_builtin_uniform(&field("kind"), kind);
+            program.set_builtin_uniform(&field("position"), light.position);
+            program.set_builtin_uniform(&field("direction"), light.direction);
+            program.set_builtin_uniform(&field("color"), light.color);
+            program.set_builtin_uniform(&field("intensity"), light.intensity);
+            program.set_builtin_uniform(&field("innerCutoff"), inner);
+            program.set_builtin_uniform(&field("outerCutoff"), outer);
+            let shadow_map = shadow_maps.iter().position(|m| m.light() == i).map_or(-1, |m| m as i32);
+            program.set_builtin_uniform(&field("shadowMap"), shadow_map);
+            count += 1;
+        }
+        program.set_builtin_uniform("lightCount", count);
+    }
+}
+
+impl Positioned for Light {
+    fn translate_by(&mut self, v: Vec3) {
+        self.position = self.position + v;
+    }
+}
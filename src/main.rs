@@ -1,17 +1,15 @@
 use std::ffi::CStr;
-use traits::*;
-use objects::*;
-use window::*;
-use crate::math::{Camera, Vec3};
-
-mod window;
-mod traits;
-mod math;
-mod objects;
-
-mod gl {
-    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
-}
+use graphics::capture::RecordingOutput;
+use graphics::clock::FrameClock;
+use graphics::context::GlConfig;
+use graphics::demo::{build_scene, spin};
+use graphics::headless::Headless;
+use graphics::input::{Event, MouseButton};
+use graphics::math::{Camera, Degrees, Vec3};
+use graphics::shader::{self, ShaderCache};
+use graphics::watch::FileWatcher;
+use graphics::window::WindowBuilder;
+use x11::keysym::{XK_Escape, XK_F10, XK_F12, XK_a, XK_d, XK_s, XK_w};
 
 unsafe extern "C" fn err(d: *mut x11::xlib::Display, e: *mut x11::xlib::XErrorEvent) -> core::ffi::c_int {
     let cc = [0i8; 1000].as_mut_ptr();
@@ -20,40 +18,118 @@ unsafe extern "C" fn err(d: *mut x11::xlib::Display, e: *mut x11::xlib::XErrorEv
     0
 }
 
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     unsafe { unsafe_main() }
 }
 
-unsafe fn unsafe_main() {
+unsafe fn unsafe_main() -> Result<(), Box<dyn std::error::Error>> {
+    let gl_config = GlConfig { samples: 4, debug: cfg!(debug_assertions), ..GlConfig::default() };
+    // `--screenshot <path>` renders one frame without a display and saves it as PNG or PPM.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|a| a == "--screenshot") {
+        let path = args.get(i + 1).ok_or("--screenshot needs an output path")?;
+        return screenshot(path, &gl_config);
+    }
+
     x11::xlib::XSetErrorHandler(Some(err));
-    let window = Window::new(false);
+    let build = |config| WindowBuilder::new().size(1920, 1080).title("graphics").gl_config(config).build();
+    // Not every driver offers MSAA; run without it rather than not at all.
+    let window = match build(gl_config) {
+        Err(e) if gl_config.samples > 0 => {
+            eprintln!("{} Falling back to no MSAA.", e);
+            build(GlConfig { samples: 0, ..gl_config })?
+        }
+        result => result?,
+    };
     window.show();
-    window.init_glx().unwrap();
-
-    let camera = Camera::new(16.0 / 9.0, 70.0, 1.0, 100.0);
-
-    let mut sphere = Sphere::new(6);
-    sphere.scaled_by(2.0);
-    sphere.rotate_around(45.0, Vec3::front());
-    sphere.translate_by(Vec3(5.0, 0.0, -10.0));
-
-    let mut sphere2 = Sphere::new(6);
-    sphere2.scaled_by(2.0);
-    sphere2.rotate_around(-45.0, Vec3::front());
-    sphere2.translate_by(Vec3(-5.0, 0.0, -10.0));
-
-    while !window.close() {
-        sphere.draw(&camera);
-        sphere.translate_by(Vec3(-5.0, 0.0,  10.0));
-        sphere.rotate_around(1.0, Vec3::up());
-        sphere.rotate_around(1.0, Vec3::front());
-        sphere.translate_by(Vec3( 5.0, 0.0, -10.0));
-
-        sphere2.draw(&camera);
-        sphere2.translate_by(Vec3( 5.0, 0.0,  10.0));
-        sphere2.rotate_around(-1.0, Vec3::up());
-        sphere2.rotate_around(-1.0, Vec3::front());
-        sphere2.translate_by(Vec3(-5.0, 0.0, -10.0));
-        window.swap_buffers(60);
+    window.init_glx()?;
+    // Fall back to sleeping when vsync isn't available.
+    let mut clock = match window.set_vsync(true) {
+        Ok(()) => FrameClock::new(None),
+        Err(e) => {
+            eprintln!("{}", e);
+            FrameClock::new(Some(60.0))
+        }
+    };
+
+    let mut camera = Camera::new(window.aspect(), Degrees(70.0), 1.0, 100.0);
+    camera.look_at(Vec3(0.0, 0.0, 10.0), Vec3::zero(), Vec3::up());
+    let (mut scene, spheres) = build_scene()?;
+
+    // Dev builds pick up shader edits between frames, unless the shaders are embedded.
+    let watcher = if cfg!(debug_assertions) && !shader::EMBEDDED {
+        FileWatcher::new("shaders").map_err(|e| eprintln!("Shader hot reload disabled: {}", e)).ok()
+    } else {
+        None
+    };
+
+    let mut screenshots = 0;
+    'main: loop {
+        let mut screenshot = false;
+        for event in window.poll_events() {
+            match event {
+                Event::Close => break 'main,
+                Event::KeyDown { keysym, .. } if keysym == XK_Escape => break 'main,
+                // F12 saves a screenshot, F10 starts and stops recording numbered frames.
+                Event::KeyDown { keysym, repeat: false } if keysym == XK_F12 => screenshot = true,
+                Event::KeyDown { keysym, repeat: false } if keysym == XK_F10 => {
+                    let result = if window.is_recording() {
+                        window.stop_recording().map(|frames| println!("Recorded {} frames", frames))
+                    } else {
+                        window.start_recording(RecordingOutput::frames("recording"))
+                    };
+                    if let Err(e) = result {
+                        eprintln!("{}", e);
+                    }
+                }
+                Event::Resize { .. } => camera.set_aspect(window.aspect()),
+                _ => {}
+            }
+        }
+
+        // Drag to orbit, scroll to zoom, WASD to move.
+        let input = window.input();
+        if input.is_button_down(MouseButton::Left) {
+            let (dx, dy) = input.mouse_delta();
+            camera.orbit(-0.3 * dx, -0.3 * dy);
+        }
+        camera.dolly(input.scroll().1);
+        let axis = |positive, negative| input.is_key_down(positive) as i32 as f32 - input.is_key_down(negative) as i32 as f32;
+        let speed = 6.0 * clock.delta();
+        camera.pan(speed * axis(XK_d, XK_a), 0.0);
+        camera.dolly(speed * axis(XK_w, XK_s));
+
+        if let Some(watcher) = &watcher {
+            let changed = watcher.changed();
+            if !changed.is_empty() {
+                for error in ShaderCache::with(|cache| cache.reload(&changed)) {
+                    eprintln!("{}", error);
+                }
+            }
+        }
+        scene.draw(&camera);
+        if screenshot {
+            let path = format!("screenshot_{}.png", screenshots);
+            match window.capture_frame().save(&path) {
+                Ok(()) => screenshots += 1,
+                Err(e) => eprintln!("Couldn't write {}: {}", path, e),
+            }
+        }
+
+        // 60 degrees per second.
+        spin(&mut scene, spheres, 60.0 * clock.delta());
+        window.swap_buffers();
+        clock.wait();
     }
+    Ok(())
+}
+
+fn screenshot(path: &str, gl_config: &GlConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let headless = Headless::new(1920, 1080, gl_config)?;
+    let mut camera = Camera::new(headless.aspect(), Degrees(70.0), 1.0, 100.0);
+    camera.look_at(Vec3(0.0, 0.0, 10.0), Vec3::zero(), Vec3::up());
+    let (scene, _) = build_scene()?;
+    scene.draw(&camera);
+    headless.capture_frame().save(path)?;
+    Ok(())
 }
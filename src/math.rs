@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::iter::Sum;
 use std::ops::{Add, Div, Mul, MulAssign, Neg, Sub};
 
@@ -210,8 +211,138 @@ impl Mat4x4 {
     }
 }
 
-impl MulAssign for Mat4x4 {
-    fn mul_assign(&mut self, rhs: Self) {
+impl Mat4x4 {
+    pub fn transpose(&self) -> Mat4x4 {
+        let mut m = [0.0; 16];
+        for y in 0..4 {
+            for x in 0..4 {
+                m[x * 4 + y] = self.0[y * 4 + x];
+            }
+        }
+        Mat4x4(m)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self.0;
+        let adj = self.adjugate();
+        m[0] * adj[0] + m[1] * adj[4] + m[2] * adj[8] + m[3] * adj[12]
+    }
+
+    /// Returns `None` when the matrix is singular. The determinant is compared against
+    /// Hadamard's bound, so scaled-down but regular matrices are still invertible.
+    pub fn inverse(&self) -> Option<Mat4x4> {
+        let m = &self.0;
+        let adj = self.adjugate();
+        let det = m[0] * adj[0] + m[1] * adj[4] + m[2] * adj[8] + m[3] * adj[12];
+        let norm = |i: fn(usize, usize) -> usize| -> f32 {
+            (0..4).map(|a| (0..4).map(|b| m[i(a, b)].powi(2)).sum::<f32>().sqrt()).product()
+        };
+        let bound = norm(|y, x| y * 4 + x).min(norm(|x, y| y * 4 + x));
+        if !det.is_finite() || det.abs() <= 4.0 * f32::EPSILON * bound {
+            return None;
+        }
+        Some(Mat4x4(adj.map(|v| v / det)))
+    }
+
+    /// Transforms `p` as a point (w = 1), dividing by the resulting w.
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.0;
+        let w = m[12] * p.x() + m[13] * p.y() + m[14] * p.z() + m[15];
+        let v = self.transform_vector(p) + Vec3(m[3], m[7], m[11]);
+        if w == 0.0 || w == 1.0 { v } else { v / w }
+    }
+
+    /// Transforms `v` as a direction (w = 0), ignoring translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3(
+            m[0] * v.x() + m[1] * v.y() + m[2] * v.z(),
+            m[4] * v.x() + m[5] * v.y() + m[6] * v.z(),
+            m[8] * v.x() + m[9] * v.y() + m[10] * v.z(),
+        )
+    }
+
+    pub fn from_trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Mat4x4 {
+        let mut m = Mat4x4::identity();
+        m.scale(scale);
+        let mut m = &rotation.to_matrix() * &m;
+        m.translate(translation);
+        m
+    }
+
+    /// Splits a matrix built from translation, rotation and scale back into those parts.
+    /// Mirroring is folded into a negative x scale.
+    pub fn decompose(&self) -> (Vec3, Quat, Vec3) {
+        let m = &self.0;
+        let translation = Vec3(m[3], m[7], m[11]);
+        let mut scale = Vec3(
+            Vec3(m[0], m[4], m[8]).len(),
+            Vec3(m[1], m[5], m[9]).len(),
+            Vec3(m[2], m[6], m[10]).len(),
+        );
+        let determinant = m[0] * (m[5] * m[10] - m[6] * m[9])
+            - m[1] * (m[4] * m[10] - m[6] * m[8])
+            + m[2] * (m[4] * m[9] - m[5] * m[8]);
+        if determinant < 0.0 {
+            scale.0 = -scale.0;
+        }
+        let mut rotation = Mat4x4::identity();
+        for y in 0..3 {
+            for (x, s) in [scale.x(), scale.y(), scale.z()].into_iter().enumerate() {
+                rotation.0[y * 4 + x] = if s == 0.0 { 0.0 } else { m[y * 4 + x] / s };
+            }
+        }
+        (translation, Quat::from_matrix(&rotation), scale)
+    }
+
+    fn adjugate(&self) -> [f32; 16] {
+        let m = &self.0;
+        let mut inv = [0.0; 16];
+
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14] + m[13] * m[6] * m[11] - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14] - m[12] * m[6] * m[11] + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13] + m[12] * m[5] * m[11] - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13] - m[12] * m[5] * m[10] + m[12] * m[6] * m[9];
+
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14] - m[13] * m[2] * m[11] + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14] + m[12] * m[2] * m[11] - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13] - m[12] * m[1] * m[11] + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13] + m[12] * m[1] * m[10] - m[12] * m[2] * m[9];
+
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14] + m[13] * m[2] * m[7] - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14] - m[12] * m[2] * m[7] + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13] + m[12] * m[1] * m[7] - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13] - m[12] * m[1] * m[6] + m[12] * m[2] * m[5];
+
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10] - m[9] * m[2] * m[7] + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10] + m[8] * m[2] * m[7] - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9] - m[8] * m[1] * m[7] + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9] + m[8] * m[1] * m[6] - m[8] * m[2] * m[5];
+
+        inv
+    }
+}
+
+impl Mul for &Mat4x4 {
+    type Output = Mat4x4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
         let a = self.0;
         let b = rhs.0;
         let mut c = [0.0; 16];
@@ -222,33 +353,850 @@ impl MulAssign for Mat4x4 {
                 }
             }
         }
-        self.0 = c;
+        Mat4x4(c)
+    }
+}
+
+impl Mul for Mat4x4 {
+    type Output = Mat4x4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl MulAssign for Mat4x4 {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = &*self * &rhs;
+    }
+}
+
+/// Rotation quaternion stored as `(x, y, z, w)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat(pub f32, pub f32, pub f32, pub f32);
+
+impl Quat {
+    pub fn identity() -> Quat {
+        Quat(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Rotation of `d` degrees around `axis`, matching `Mat4x4::rotate`. A zero axis has no
+    /// direction to turn around and gives the identity.
+    pub fn from_axis_angle(d: f32, axis: Vec3) -> Quat {
+        if axis.len_squared() < f32::EPSILON {
+            return Quat::identity();
+        }
+        let half = radians(d) * 0.5;
+        let v = half.sin() * axis.normalized();
+        Quat(v.x(), v.y(), v.z(), half.cos())
+    }
+
+    /// Rotation from euler angles in degrees: `roll` around z, then `pitch` around x,
+    /// then `yaw` around y.
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Quat {
+        Quat::from_axis_angle(yaw, Vec3::up())
+            * Quat::from_axis_angle(pitch, Vec3(1.0, 0.0, 0.0))
+            * Quat::from_axis_angle(roll, Vec3(0.0, 0.0, 1.0))
+    }
+
+    /// Extracts the rotation from the upper 3x3 of an orthonormal matrix.
+    pub fn from_matrix(m: &Mat4x4) -> Quat {
+        let m = &m.0;
+        let trace = m[0] + m[5] + m[10];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat((m[9] - m[6]) / s, (m[2] - m[8]) / s, (m[4] - m[1]) / s, 0.25 * s)
+        } else if m[0] > m[5] && m[0] > m[10] {
+            let s = (1.0 + m[0] - m[5] - m[10]).sqrt() * 2.0;
+            Quat(0.25 * s, (m[1] + m[4]) / s, (m[2] + m[8]) / s, (m[9] - m[6]) / s)
+        } else if m[5] > m[10] {
+            let s = (1.0 + m[5] - m[0] - m[10]).sqrt() * 2.0;
+            Quat((m[1] + m[4]) / s, 0.25 * s, (m[6] + m[9]) / s, (m[2] - m[8]) / s)
+        } else {
+            let s = (1.0 + m[10] - m[0] - m[5]).sqrt() * 2.0;
+            Quat((m[2] + m[8]) / s, (m[6] + m[9]) / s, 0.25 * s, (m[4] - m[1]) / s)
+        };
+        q.normalized()
+    }
+
+    pub fn x(self) -> f32 {
+        self.0
+    }
+
+    pub fn y(self) -> f32 {
+        self.1
+    }
+
+    pub fn z(self) -> f32 {
+        self.2
+    }
+
+    pub fn w(self) -> f32 {
+        self.3
+    }
+
+    pub fn dot(self, q: Quat) -> f32 {
+        self.x() * q.x() + self.y() * q.y() + self.z() * q.z() + self.w() * q.w()
+    }
+
+    pub fn len(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(self) -> Quat {
+        let len = self.len();
+        Quat(self.x() / len, self.y() / len, self.z() / len, self.w() / len)
+    }
+
+    /// The inverse of a unit quaternion.
+    pub fn conjugate(self) -> Quat {
+        Quat(-self.x(), -self.y(), -self.z(), self.w())
+    }
+
+    /// Spherical interpolation along the shorter arc.
+    pub fn slerp(self, q: Quat, t: f32) -> Quat {
+        let mut cos = self.dot(q);
+        let q = if cos < 0.0 {
+            cos = -cos;
+            Quat(-q.x(), -q.y(), -q.z(), -q.w())
+        } else {
+            q
+        };
+        let (a, b) = if cos > 0.9995 {
+            // Nearly parallel, fall back to linear interpolation.
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quat(
+            a * self.x() + b * q.x(),
+            a * self.y() + b * q.y(),
+            a * self.z() + b * q.z(),
+            a * self.w() + b * q.w(),
+        ).normalized()
+    }
+
+    pub fn rotate_vector(self, v: Vec3) -> Vec3 {
+        let u = Vec3(self.x(), self.y(), self.z());
+        let t = 2.0 * u.cross(v);
+        v + self.w() * t + u.cross(t)
+    }
+
+    pub fn to_matrix(self) -> Mat4x4 {
+        let Quat(x, y, z, w) = self;
+        let mut m = Mat4x4::identity();
+        m.0[0] = 1.0 - 2.0 * (y * y + z * z);
+        m.0[1] = 2.0 * (x * y - z * w);
+        m.0[2] = 2.0 * (x * z + y * w);
+        m.0[4] = 2.0 * (x * y + z * w);
+        m.0[5] = 1.0 - 2.0 * (x * x + z * z);
+        m.0[6] = 2.0 * (y * z - x * w);
+        m.0[8] = 2.0 * (x * z - y * w);
+        m.0[9] = 2.0 * (y * z + x * w);
+        m.0[10] = 1.0 - 2.0 * (x * x + y * y);
+        m
+    }
+}
+
+/// Composition: `a * b` rotates by `b` first, then by `a`, like matrix multiplication.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let Quat(ax, ay, az, aw) = self;
+        let Quat(bx, by, bz, bw) = rhs;
+        Quat(
+            aw * bx + ax * bw + ay * bz - az * by,
+            aw * by - ax * bz + ay * bw + az * bx,
+            aw * bz + ax * by - ay * bx + az * bw,
+            aw * bw - ax * bx - ay * by - az * bz,
+        )
+    }
+}
+
+/// Translation, rotation and scale kept apart; the matrix is composed on demand and cached.
+#[derive(Debug, Clone)]
+pub struct Trs {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    matrix: Cell<Option<[f32; 16]>>,
+}
+
+impl Trs {
+    pub fn identity() -> Self {
+        Self {
+            translation: Vec3::zero(),
+            rotation: Quat::identity(),
+            scale: Vec3(1.0, 1.0, 1.0),
+            matrix: Cell::new(None),
+        }
+    }
+
+    pub fn from_matrix(matrix: &Mat4x4) -> Self {
+        let (translation, rotation, scale) = matrix.decompose();
+        Self {
+            translation,
+            rotation,
+            scale,
+            matrix: Cell::new(None),
+        }
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        self.translation
+    }
+
+    pub fn set_translation(&mut self, translation: Vec3) {
+        self.translation = translation;
+        self.matrix.set(None);
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quat) {
+        self.rotation = rotation.normalized();
+        self.matrix.set(None);
+    }
+
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: Vec3) {
+        self.scale = scale;
+        self.matrix.set(None);
+    }
+
+    pub fn matrix(&self) -> Mat4x4 {
+        if let Some(m) = self.matrix.get() {
+            return Mat4x4(m);
+        }
+        let m = Mat4x4::from_trs(self.translation, self.rotation, self.scale);
+        self.matrix.set(Some(m.0));
+        m
     }
 }
 
+impl Default for Trs {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Degrees(pub f32);
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Radians(pub f32);
+
+impl From<Degrees> for Radians {
+    fn from(d: Degrees) -> Self {
+        Radians(radians(d.0))
+    }
+}
+
+impl From<Radians> for Degrees {
+    fn from(r: Radians) -> Self {
+        Degrees(degrees(r.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective { fov: Radians, near: f32, far: f32 },
+    Orthographic { left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32 },
+}
+
 pub struct Camera {
     pub view: Mat4x4,
     pub projection: Mat4x4,
+    position: Vec3,
+    target: Vec3,
+    up: Vec3,
+    aspect: f32,
+    kind: Projection,
 }
 
 impl Camera {
-    pub fn new(aspect: f32, fov: f32, near: f32, far: f32) -> Self {
-        let mut projection = Mat4x4::identity();
+    /// Pitch is kept this many degrees away from straight up or down, where `look_at` degenerates.
+    const PITCH_LIMIT: f32 = 1.0;
+    /// `dolly` stops at least this far from the target, even with a near plane at 0.
+    const MIN_DISTANCE: f32 = 1e-3;
 
-        projection.0[0] = 1.0 / (aspect * (fov * 0.5).tan());
-        projection.0[5] = 1.0 / (fov * 0.5).tan();
-        projection.0[10] = -(far + near) / (far - near);
-        projection.0[11] = -(2.0 * far * near) / (far - near);
-        projection.0[14] = -1.0;
-        projection.0[15] = 0.0;
+    /// Perspective camera. `fov` is the vertical field of view, e.g. `Degrees(70.0)`.
+    pub fn new(aspect: f32, fov: impl Into<Radians>, near: f32, far: f32) -> Self {
+        Self::with_projection(aspect, Projection::Perspective { fov: fov.into(), near, far })
+    }
 
-        Self {
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let aspect = (right - left) / (top - bottom);
+        Self::with_projection(aspect, Projection::Orthographic { left, right, bottom, top, near, far })
+    }
+
+    fn with_projection(aspect: f32, kind: Projection) -> Self {
+        let mut camera = Self {
             view: Mat4x4::identity(),
-            projection,
+            projection: Mat4x4::identity(),
+            position: Vec3::zero(),
+            target: Vec3::back(),
+            up: Vec3::up(),
+            aspect,
+            kind,
+        };
+        camera.update_projection();
+        camera
+    }
+
+    pub fn projection_kind(&self) -> Projection {
+        self.kind
+    }
+
+    pub fn set_projection_kind(&mut self, kind: Projection) {
+        self.kind = kind;
+        if let Projection::Orthographic { left, right, bottom, top, .. } = kind {
+            self.aspect = (right - left) / (top - bottom);
         }
+        self.update_projection();
+    }
+
+    pub fn near(&self) -> f32 {
+        match self.kind {
+            Projection::Perspective { near, .. } | Projection::Orthographic { near, .. } => near,
+        }
+    }
+
+    pub fn far(&self) -> f32 {
+        match self.kind {
+            Projection::Perspective { far, .. } | Projection::Orthographic { far, .. } => far,
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.up
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        (self.target - self.position).normalized()
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.forward().cross(self.up).normalized()
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    /// Orthographic cameras keep their vertical extent and center and widen or narrow horizontally.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+        if let Projection::Orthographic { left, right, bottom, top, .. } = &mut self.kind {
+            let center = (*left + *right) / 2.0;
+            let half_width = (*top - *bottom) * aspect / 2.0;
+            (*left, *right) = (center - half_width, center + half_width);
+        }
+        self.update_projection();
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.look_at(position, self.target, self.up);
+    }
+
+    pub fn look_at(&mut self, eye: Vec3, target: Vec3, up: Vec3) {
+        self.position = eye;
+        self.target = target;
+        self.up = up.normalized();
+        self.update_view();
+    }
+
+    /// Rotates the camera around its target by `yaw` degrees about `up` and `pitch` degrees
+    /// about `right`, keeping the distance to the target.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let offset = self.position - self.target;
+        let offset = self.turn(offset, yaw, pitch);
+        self.position = self.target + offset;
+        self.update_view();
+    }
+
+    /// Moves both the camera and its target along the view plane.
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let camera_up = self.right().cross(self.forward());
+        let offset = right * self.right() + up * camera_up;
+        self.position = self.position + offset;
+        self.target = self.target + offset;
+        self.update_view();
+    }
+
+    /// Moves the camera towards its target, stopping at the near plane's distance from it.
+    pub fn dolly(&mut self, distance: f32) {
+        let offset = self.target - self.position;
+        let remaining = offset.len();
+        // On the target there is no direction to move in.
+        if remaining < f32::EPSILON {
+            return;
+        }
+        let distance = distance.min(remaining - self.near().max(Self::MIN_DISTANCE));
+        self.position = self.position + distance * (offset / remaining);
+        self.update_view();
+    }
+
+    /// Turns the camera in place by `d` degrees around `up`, as in a first-person view.
+    pub fn yaw(&mut self, d: f32) {
+        let offset = self.turn(self.target - self.position, d, 0.0);
+        self.target = self.position + offset;
+        self.update_view();
+    }
+
+    /// Tilts the camera in place by `d` degrees, stopping short of looking straight up or down.
+    pub fn pitch(&mut self, d: f32) {
+        let offset = self.turn(self.target - self.position, 0.0, d);
+        self.target = self.position + offset;
+        self.update_view();
+    }
+
+    fn turn(&self, offset: Vec3, yaw: f32, pitch: f32) -> Vec3 {
+        if offset.len_squared() < f32::EPSILON {
+            return offset;
+        }
+        let current = degrees(offset.normalized().dot(self.up).clamp(-1.0, 1.0).acos());
+        let limited = (current - pitch).clamp(Self::PITCH_LIMIT, 180.0 - Self::PITCH_LIMIT);
+        let pitch = current - limited;
+
+        // Looking straight along `up`, any horizontal axis tilts the view back into range.
+        let mut axis = offset.cross(self.up);
+        if axis.len_squared() < f32::EPSILON {
+            axis = self.up.cross(if self.up.x().abs() < 0.9 { Vec3(1.0, 0.0, 0.0) } else { Vec3(0.0, 1.0, 0.0) });
+        }
+        let mut m = Mat4x4::identity();
+        m.rotate(pitch, axis.normalized());
+        m.rotate(yaw, self.up);
+        m.transform_vector(offset)
+    }
+
+    fn update_view(&mut self) {
+        let f = self.forward();
+        let s = self.right();
+        let u = s.cross(f);
+        let e = self.position;
+        self.view = Mat4x4([
+            s.x(), s.y(), s.z(), -s.dot(e),
+            u.x(), u.y(), u.z(), -u.dot(e),
+            -f.x(), -f.y(), -f.z(), f.dot(e),
+            0.0, 0.0, 0.0, 1.0,
+        ]);
+    }
+
+    fn update_projection(&mut self) {
+        let mut projection = Mat4x4::identity();
+
+        match self.kind {
+            Projection::Perspective { fov: Radians(fov), near, far } => {
+                projection.0[0] = 1.0 / (self.aspect * (fov * 0.5).tan());
+                projection.0[5] = 1.0 / (fov * 0.5).tan();
+                projection.0[10] = -(far + near) / (far - near);
+                projection.0[11] = -(2.0 * far * near) / (far - near);
+                projection.0[14] = -1.0;
+                projection.0[15] = 0.0;
+            }
+            Projection::Orthographic { left, right, bottom, top, near, far } => {
+                projection.0[0] = 2.0 / (right - left);
+                projection.0[3] = -(right + left) / (right - left);
+                projection.0[5] = 2.0 / (top - bottom);
+                projection.0[7] = -(top + bottom) / (top - bottom);
+                projection.0[10] = -2.0 / (far - near);
+                projection.0[11] = -(far + near) / (far - near);
+            }
+        }
+
+        self.projection = projection;
     }
 }
 
 pub fn radians(degree: f32) -> f32 {
     std::f32::consts::PI * degree / 180.0
-}
\ No newline at end of file
+}
+
+pub fn degrees(radian: f32) -> f32 {
+    180.0 * radian / std::f32::consts::PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small deterministic generator so the property tests are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, low: f32, high: f32) -> f32 {
+            low + (high - low) * self.next()
+        }
+
+        fn vec3(&mut self, low: f32, high: f32) -> Vec3 {
+            Vec3(self.range(low, high), self.range(low, high), self.range(low, high))
+        }
+
+        fn transform(&mut self) -> Mat4x4 {
+            let mut m = Mat4x4::identity();
+            m.scale(self.vec3(0.1, 5.0));
+            m.rotate(self.range(-180.0, 180.0), self.vec3(-1.0, 1.0).normalized());
+            m.translate(self.vec3(-50.0, 50.0));
+            m
+        }
+
+        fn matrix(&mut self) -> Mat4x4 {
+            let mut m = [0.0; 16];
+            m.iter_mut().for_each(|v| *v = self.range(-10.0, 10.0));
+            Mat4x4(m)
+        }
+    }
+
+    fn assert_close(a: &Mat4x4, b: &Mat4x4, epsilon: f32) {
+        for (x, y) in a.0.iter().zip(b.0.iter()) {
+            assert!((x - y).abs() <= epsilon, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn inverse_of_transforms_is_identity() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..1000 {
+            let m = rng.transform();
+            let inverse = m.inverse().unwrap();
+            assert_close(&(&m * &inverse), &Mat4x4::identity(), 1e-3);
+            assert_close(&(&inverse * &m), &Mat4x4::identity(), 1e-3);
+        }
+    }
+
+    #[test]
+    fn inverse_of_general_matrices_is_identity() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..1000 {
+            let m = rng.matrix();
+            // Nearly singular matrices are legitimately imprecise, skip them.
+            if m.determinant().abs() < 1.0 { continue; }
+            let inverse = m.inverse().unwrap();
+            assert_close(&(&m * &inverse), &Mat4x4::identity(), 1e-2);
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let mut m = Mat4x4::identity();
+        m.scale(Vec3(1.0, 0.0, 1.0));
+        assert!(m.inverse().is_none());
+
+        let mut rng = Rng(42);
+        let mut m = rng.matrix();
+        for x in 0..4 {
+            m.0[4 + x] = 2.0 * m.0[x];
+        }
+        assert!(m.inverse().is_none());
+    }
+
+    #[test]
+    fn determinant_is_multiplicative() {
+        let mut rng = Rng(7);
+        for _ in 0..1000 {
+            let (a, b) = (rng.transform(), rng.transform());
+            let expected = a.determinant() * b.determinant();
+            let actual = (&a * &b).determinant();
+            assert!((expected - actual).abs() <= 1e-3 * expected.abs());
+            assert!((a.determinant() - a.transpose().determinant()).abs() <= 1e-3 * a.determinant().abs());
+        }
+
+        let mut m = Mat4x4::identity();
+        m.scale(Vec3(2.0, 3.0, 4.0));
+        m.rotate(30.0, Vec3::up());
+        m.translate(Vec3(5.0, 6.0, 7.0));
+        assert!((m.determinant() - 24.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn transpose_is_an_involution() {
+        let mut rng = Rng(11);
+        let m = rng.matrix();
+        assert_eq!(m.transpose().transpose().0, m.0);
+        assert_eq!(m.transpose().0[1], m.0[4]);
+    }
+
+    #[test]
+    fn mul_matches_mul_assign() {
+        let mut rng = Rng(13);
+        let (a, b) = (rng.matrix(), rng.matrix());
+        let mut c = a.clone();
+        c *= b.clone();
+        assert_eq!((a * b).0, c.0);
+    }
+
+    #[test]
+    fn transform_point_and_vector() {
+        let mut m = Mat4x4::identity();
+        m.scale(Vec3(2.0, 2.0, 2.0));
+        m.translate(Vec3(1.0, 2.0, 3.0));
+        assert_eq!(m.transform_point(Vec3(1.0, 1.0, 1.0)), Vec3(3.0, 4.0, 5.0));
+        assert_eq!(m.transform_vector(Vec3(1.0, 1.0, 1.0)), Vec3(2.0, 2.0, 2.0));
+
+        let camera = Camera::new(1.0, Degrees(90.0), 1.0, 100.0);
+        let near = camera.projection.transform_point(Vec3(0.0, 0.0, -1.0));
+        let far = camera.projection.transform_point(Vec3(0.0, 0.0, -100.0));
+        assert!((near.z() + 1.0).abs() < 1e-5);
+        assert!((far.z() - 1.0).abs() < 1e-5);
+    }
+
+    fn assert_vec_close(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn default_camera_view_is_identity() {
+        let camera = Camera::new(1.0, Radians(1.0), 1.0, 100.0);
+        assert_close(&camera.view, &Mat4x4::identity(), 1e-6);
+    }
+
+    #[test]
+    fn look_at_maps_eye_to_origin_and_target_ahead() {
+        let mut camera = Camera::new(1.0, Radians(1.0), 1.0, 100.0);
+        camera.look_at(Vec3(3.0, 4.0, 5.0), Vec3(-1.0, 0.0, 2.0), Vec3::up());
+        assert_vec_close(camera.view.transform_point(Vec3(3.0, 4.0, 5.0)), Vec3::zero());
+        let distance = (Vec3(-1.0, 0.0, 2.0) - Vec3(3.0, 4.0, 5.0)).len();
+        assert_vec_close(camera.view.transform_point(Vec3(-1.0, 0.0, 2.0)), Vec3(0.0, 0.0, -distance));
+    }
+
+    #[test]
+    fn orbit_keeps_distance_and_clamps_pitch() {
+        let mut camera = Camera::new(1.0, Radians(1.0), 1.0, 100.0);
+        camera.look_at(Vec3(0.0, 0.0, 10.0), Vec3::zero(), Vec3::up());
+        camera.orbit(90.0, 0.0);
+        assert_vec_close(camera.position(), Vec3(10.0, 0.0, 0.0));
+        camera.orbit(0.0, 500.0);
+        assert!((camera.position().len() - 10.0).abs() < 1e-3);
+        assert!(camera.forward().dot(Vec3::down()) < 1.0);
+        assert!(camera.position().y() > 9.9);
+    }
+
+    #[test]
+    fn pan_dolly_and_first_person_turns() {
+        let mut camera = Camera::new(1.0, Radians(1.0), 1.0, 100.0);
+        camera.look_at(Vec3(0.0, 0.0, 10.0), Vec3::zero(), Vec3::up());
+        camera.pan(2.0, 3.0);
+        assert_vec_close(camera.position(), Vec3(2.0, 3.0, 10.0));
+        assert_vec_close(camera.target(), Vec3(2.0, 3.0, 0.0));
+        camera.dolly(4.0);
+        assert_vec_close(camera.position(), Vec3(2.0, 3.0, 6.0));
+        camera.dolly(100.0);
+        assert_vec_close(camera.position(), Vec3(2.0, 3.0, 1.0));
+
+        camera.look_at(Vec3::zero(), Vec3::back(), Vec3::up());
+        camera.yaw(90.0);
+        assert_vec_close(camera.forward(), Vec3(-1.0, 0.0, 0.0));
+        camera.pitch(45.0);
+        assert!((camera.forward().dot(Vec3::up()) - radians(45.0).sin()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn dolly_stops_short_of_the_target() {
+        let mut camera = Camera::orthographic(-1.0, 1.0, -1.0, 1.0, 0.0, 100.0);
+        camera.look_at(Vec3(0.0, 0.0, 10.0), Vec3::zero(), Vec3::up());
+        camera.dolly(100.0);
+        assert!(camera.position().z() > 0.0);
+        camera.dolly(100.0);
+        assert!(camera.view.0.iter().all(|v| v.is_finite()));
+
+        // A camera sitting on its target stays put instead of turning into NaN.
+        camera.look_at(Vec3::zero(), Vec3::zero(), Vec3::up());
+        camera.dolly(1.0);
+        assert_eq!(camera.position(), Vec3::zero());
+    }
+
+    #[test]
+    fn turns_recover_from_looking_along_up() {
+        let mut camera = Camera::new(1.0, Radians(1.0), 1.0, 100.0);
+        camera.look_at(Vec3(0.0, 10.0, 0.0), Vec3::zero(), Vec3::up());
+        camera.orbit(30.0, 0.0);
+        assert!(camera.view.0.iter().all(|v| v.is_finite()));
+        assert!((camera.position().len() - 10.0).abs() < 1e-3);
+        assert!(camera.forward().dot(Vec3::down()) < radians(90.0 - Camera::PITCH_LIMIT).sin() + 1e-4);
+
+        camera.look_at(Vec3::zero(), Vec3::down(), Vec3::up());
+        camera.pitch(-10.0);
+        assert!(camera.view.0.iter().all(|v| v.is_finite()));
+        camera.pitch(-500.0);
+        let limit = radians(90.0 - Camera::PITCH_LIMIT).sin();
+        assert!((camera.forward().dot(Vec3::down()) - limit).abs() < 1e-4);
+    }
+
+    #[test]
+    fn set_aspect_updates_projection() {
+        let mut camera = Camera::new(1.0, Radians(1.0), 1.0, 100.0);
+        let before = camera.projection.0[0];
+        camera.set_aspect(2.0);
+        assert!((camera.projection.0[0] - before / 2.0).abs() < 1e-6);
+        assert_eq!(camera.projection.0[5], Camera::new(1.0, Radians(1.0), 1.0, 100.0).projection.0[5]);
+    }
+
+    #[test]
+    fn fov_units_agree() {
+        let degrees = Camera::new(1.5, Degrees(70.0), 1.0, 100.0);
+        let radians = Camera::new(1.5, Radians(radians(70.0)), 1.0, 100.0);
+        assert_close(&degrees.projection, &radians.projection, 1e-6);
+        // A 90 degree vertical fov puts the top of the near plane at y = near.
+        let camera = Camera::new(1.0, Degrees(90.0), 1.0, 100.0);
+        assert_vec_close(camera.projection.transform_point(Vec3(1.0, 1.0, -1.0)), Vec3(1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn orthographic_maps_box_to_clip_space() {
+        let camera = Camera::orthographic(-4.0, 4.0, -2.0, 2.0, 0.5, 10.0);
+        assert_eq!(camera.aspect(), 2.0);
+        assert_vec_close(camera.projection.transform_point(Vec3(-4.0, -2.0, -0.5)), Vec3(-1.0, -1.0, -1.0));
+        assert_vec_close(camera.projection.transform_point(Vec3(4.0, 2.0, -10.0)), Vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn orthographic_set_aspect_keeps_height() {
+        let mut camera = Camera::orthographic(0.0, 4.0, -1.0, 1.0, 0.5, 10.0);
+        camera.set_aspect(1.0);
+        assert_eq!(
+            camera.projection_kind(),
+            Projection::Orthographic { left: 1.0, right: 3.0, bottom: -1.0, top: 1.0, near: 0.5, far: 10.0 },
+        );
+    }
+
+    #[test]
+    fn quat_matches_matrix_rotation() {
+        let mut rng = Rng(19);
+        for _ in 0..1000 {
+            let d = rng.range(-360.0, 360.0);
+            let axis = rng.vec3(-1.0, 1.0).normalized();
+            let mut m = Mat4x4::identity();
+            m.rotate(d, axis);
+            let q = Quat::from_axis_angle(d, axis);
+            assert_close(&q.to_matrix(), &m, 1e-5);
+            let v = rng.vec3(-10.0, 10.0);
+            assert_vec_close(q.rotate_vector(v) / 10.0, m.transform_vector(v) / 10.0);
+            let back = Quat::from_matrix(&m);
+            assert!(back.dot(q).abs() > 1.0 - 1e-5);
+        }
+    }
+
+    #[test]
+    fn quat_composition_matches_matrix_product() {
+        let mut rng = Rng(23);
+        for _ in 0..1000 {
+            let a = Quat::from_axis_angle(rng.range(-180.0, 180.0), rng.vec3(-1.0, 1.0));
+            let b = Quat::from_axis_angle(rng.range(-180.0, 180.0), rng.vec3(-1.0, 1.0));
+            assert_close(&(a * b).to_matrix(), &(&a.to_matrix() * &b.to_matrix()), 1e-5);
+            assert!(((a * a.conjugate()).dot(Quat::identity()) - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn quat_from_euler() {
+        let q = Quat::from_euler(90.0, 0.0, 0.0);
+        assert_vec_close(q.rotate_vector(Vec3::back()), Vec3(-1.0, 0.0, 0.0));
+        let q = Quat::from_euler(0.0, 90.0, 0.0);
+        assert_vec_close(q.rotate_vector(Vec3::back()), Vec3::up());
+        let q = Quat::from_euler(90.0, 90.0, 0.0);
+        assert_vec_close(q.rotate_vector(Vec3::up()), Vec3(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn quat_around_zero_axis_is_identity() {
+        assert_eq!(Quat::from_axis_angle(45.0, Vec3::zero()), Quat::identity());
+        let mut trs = Trs::identity();
+        trs.set_rotation(Quat::from_axis_angle(30.0, Vec3::zero()) * trs.rotation());
+        assert!(trs.matrix().0.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn quat_slerp() {
+        let a = Quat::identity();
+        let b = Quat::from_axis_angle(90.0, Vec3::up());
+        assert!((a.slerp(b, 0.0).dot(a) - 1.0).abs() < 1e-6);
+        assert!((a.slerp(b, 1.0).dot(b) - 1.0).abs() < 1e-6);
+        let half = a.slerp(b, 0.5);
+        assert!((half.dot(Quat::from_axis_angle(45.0, Vec3::up())) - 1.0).abs() < 1e-6);
+        // Takes the short way round even when the signs differ.
+        let negated = Quat(-b.x(), -b.y(), -b.z(), -b.w());
+        assert!((a.slerp(negated, 0.5).dot(half).abs() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn decompose_round_trips() {
+        let mut rng = Rng(29);
+        for _ in 0..1000 {
+            let translation = rng.vec3(-50.0, 50.0);
+            let rotation = Quat::from_axis_angle(rng.range(-180.0, 180.0), rng.vec3(-1.0, 1.0));
+            let scale = rng.vec3(0.1, 5.0);
+            let m = Mat4x4::from_trs(translation, rotation, scale);
+            let (t, r, s) = m.decompose();
+            assert_vec_close(t / 50.0, translation / 50.0);
+            assert!(r.dot(rotation).abs() > 1.0 - 1e-4);
+            assert_vec_close(s, scale);
+        }
+    }
+
+    #[test]
+    fn repeated_quat_rotation_stays_orthonormal() {
+        let mut q = Quat::identity();
+        let step = Quat::from_axis_angle(1.0, Vec3(1.0, 2.0, 3.0));
+        for _ in 0..100_000 {
+            q = (step * q).normalized();
+        }
+        let m = q.to_matrix();
+        assert_close(&(&m * &m.transpose()), &Mat4x4::identity(), 1e-5);
+    }
+
+    #[test]
+    fn trs_composes_lazily_and_invalidates() {
+        let mut trs = Trs::identity();
+        assert_close(&trs.matrix(), &Mat4x4::identity(), 0.0);
+
+        let rotation = Quat::from_axis_angle(30.0, Vec3::up());
+        trs.set_translation(Vec3(1.0, 2.0, 3.0));
+        trs.set_rotation(rotation);
+        trs.set_scale(Vec3(2.0, 2.0, 2.0));
+        let expected = Mat4x4::from_trs(Vec3(1.0, 2.0, 3.0), rotation, Vec3(2.0, 2.0, 2.0));
+        assert_close(&trs.matrix(), &expected, 1e-6);
+
+        trs.set_translation(Vec3::zero());
+        assert_vec_close(trs.matrix().transform_point(Vec3::zero()), Vec3::zero());
+
+        let back = Trs::from_matrix(&expected);
+        assert_vec_close(back.translation(), Vec3(1.0, 2.0, 3.0));
+        assert_vec_close(back.scale(), Vec3(2.0, 2.0, 2.0));
+        assert_close(&back.matrix(), &expected, 1e-5);
+    }
+
+    #[test]
+    fn transform_round_trips_through_inverse() {
+        let mut rng = Rng(17);
+        for _ in 0..1000 {
+            let m = rng.transform();
+            let p = rng.vec3(-10.0, 10.0);
+            let back = m.inverse().unwrap().transform_point(m.transform_point(p));
+            assert!((back - p).len() < 1e-3);
+        }
+    }
+}
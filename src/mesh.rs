@@ -0,0 +1,138 @@
+use std::cell::Cell;
+use crate::gl;
+use crate::traits::Meshed;
+
+/// GPU copy of the geometry of a `Meshed` object.
+///
+/// The vertex array and its buffers are created on the first `bind`, uploaded
+/// again only after `mark_dirty`, and released when the mesh is dropped.
+pub struct Mesh {
+    vao: Cell<u32>,
+    vertex_buffer: Cell<u32>,
+    index_buffer: Cell<u32>,
+    normal_buffer: Cell<u32>,
+    index_count: Cell<usize>,
+    dirty: Cell<bool>,
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Self {
+            vao: Cell::new(0),
+            vertex_buffer: Cell::new(0),
+            index_buffer: Cell::new(0),
+            normal_buffer: Cell::new(0),
+            index_count: Cell::new(0),
+            dirty: Cell::new(true),
+        }
+    }
+
+    pub fn mark_dirty(&self) {
+        self.dirty.set(true);
+    }
+
+    /// Binds the vertex array, uploading `source` first if the mesh is dirty.
+    /// Returns the number of indices to draw.
+    pub fn bind<M: Meshed + ?Sized>(&self, source: &M) -> usize {
+        unsafe {
+            if self.vao.get() == 0 {
+                self.create();
+            }
+            gl::BindVertexArray(self.vao.get());
+            if self.dirty.get() {
+                self.upload(source);
+            }
+        }
+        self.index_count.get()
+    }
+
+    unsafe fn create(&self) {
+        let mut vao = 0;
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        let mut buffers = [0; 3];
+        gl::GenBuffers(3, buffers.as_mut_ptr());
+        let [vertex_buffer, index_buffer, normal_buffer] = buffers;
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+        gl::VertexAttribPointer(
+            0,
+            3,
+            gl::FLOAT,
+            gl::FALSE,
+            (3 * std::mem::size_of::<f32>()) as _,
+            core::ptr::null(),
+        );
+        gl::EnableVertexAttribArray(0);
+
+        gl::BindBuffer(gl::ARRAY_BUFFER, normal_buffer);
+        gl::VertexAttribPointer(
+            1,
+            3,
+            gl::FLOAT,
+            gl::FALSE,
+            (3 * std::mem::size_of::<f32>()) as _,
+            core::ptr::null(),
+        );
+        gl::EnableVertexAttribArray(1);
+
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+
+        self.vao.set(vao);
+        self.vertex_buffer.set(vertex_buffer);
+        self.index_buffer.set(index_buffer);
+        self.normal_buffer.set(normal_buffer);
+        self.dirty.set(true);
+    }
+
+    unsafe fn upload<M: Meshed + ?Sized>(&self, source: &M) {
+        let vertices = source.get_vertices();
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer.get());
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            std::mem::size_of_val(vertices) as _,
+            vertices.as_ptr() as _,
+            gl::STATIC_DRAW,
+        );
+
+        let normals = source.get_normals();
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.normal_buffer.get());
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            std::mem::size_of_val(normals) as _,
+            normals.as_ptr() as _,
+            gl::STATIC_DRAW,
+        );
+
+        // The element buffer binding is part of the vertex array state, which is bound here.
+        let indices = source.get_indices();
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_buffer.get());
+        gl::BufferData(
+            gl::ELEMENT_ARRAY_BUFFER,
+            std::mem::size_of_val(indices) as _,
+            indices.as_ptr() as _,
+            gl::STATIC_DRAW,
+        );
+
+        self.index_count.set(indices.len());
+        self.dirty.set(false);
+    }
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        if self.vao.get() == 0 { return; }
+        unsafe {
+            let buffers = [self.vertex_buffer.get(), self.index_buffer.get(), self.normal_buffer.get()];
+            gl::DeleteBuffers(3, buffers.as_ptr());
+            gl::DeleteVertexArrays(1, &self.vao.get());
+        }
+    }
+}
@@ -1,14 +1,39 @@
 use std::collections::HashMap;
 use crate::object;
+use crate::shader::ShaderError;
 
 object!(Sphere("shaders/galaxy.vert", "shaders/galaxy.frag") {
 
 });
 
+object!(Plane("shaders/plain.vert", "shaders/plain.frag") {
+
+});
+
+impl Plane {
+    /// A 2x2 square in the xz plane, facing up.
+    pub fn new() -> Result<Self, ShaderError> {
+        let mut plane = Plane::empty()?;
+        plane.vertices = vec![
+            -1.0, 0.0, -1.0,
+            1.0, 0.0, -1.0,
+            1.0, 0.0, 1.0,
+            -1.0, 0.0, 1.0,
+        ];
+        plane.indices = vec![
+            3, 2, 1,
+            3, 1, 0,
+        ];
+        plane.normals = [0.0, 1.0, 0.0].repeat(4);
+        Ok(plane)
+    }
+}
+
 impl Sphere {
+    #[allow(clippy::identity_op)]
     pub fn generate_icosahedron() -> Vec<f32> {
         use std::f32::consts::PI;
-        let mut vertices: Vec<f32> = std::iter::repeat(0.0).take(12 * 3).collect();
+        let mut vertices: Vec<f32> = vec![0.0; 12 * 3];
 
         let horizontal_angle = PI / 180.0 * 72.0;
         let vertical_angle = (1.0f32 / 2.0f32).atan();
@@ -50,8 +75,9 @@ impl Sphere {
         vertices
     }
 
-    pub fn new(subdivision: usize) -> Self {
-        let mut sphere = Sphere::empty();
+    #[allow(clippy::identity_op)]
+    pub fn new(subdivision: usize) -> Result<Self, ShaderError> {
+        let mut sphere = Sphere::empty()?;
         sphere.vertices = Sphere::generate_icosahedron();
         sphere.indices = vec![
             0, 1, 2,
@@ -79,17 +105,9 @@ impl Sphere {
             let mut new_index = sphere.vertices.len() as u32 / 3;
             let mut middles = HashMap::<(u32, u32), u32>::new();
             let mut temp_indices = Vec::new();
-            for (_ind, next) in sphere.indices.chunks(3).enumerate() {
+            for next in sphere.indices.chunks(3) {
                 let (f, s, t) = (next[0], next[1], next[2]);
-                let middle_fs = {
-                    if let Some(m) = middles.get(&(f, s)) {
-                        Some(m)
-                    } else if let Some(m) = middles.get(&(s, f)) {
-                        Some(m)
-                    } else {
-                        None
-                    }
-                };
+                let middle_fs = middles.get(&(f, s)).or_else(|| middles.get(&(s, f)));
                 let fs = {
                     if let Some(&m) = middle_fs {
                         m
@@ -110,15 +128,7 @@ impl Sphere {
                         i
                     }
                 };
-                let middle_st = {
-                    if let Some(m) = middles.get(&(s, t)) {
-                        Some(m)
-                    } else if let Some(m) = middles.get(&(t, s)) {
-                        Some(m)
-                    } else {
-                        None
-                    }
-                };
+                let middle_st = middles.get(&(s, t)).or_else(|| middles.get(&(t, s)));
                 let st = {
                     if let Some(&m) = middle_st {
                         m
@@ -139,15 +149,7 @@ impl Sphere {
                         i
                     }
                 };
-                let middle_ft = {
-                    if let Some(m) = middles.get(&(f, t)) {
-                        Some(m)
-                    } else if let Some(m) = middles.get(&(t, f)) {
-                        Some(m)
-                    } else {
-                        None
-                    }
-                };
+                let middle_ft = middles.get(&(f, t)).or_else(|| middles.get(&(t, f)));
                 let ft = {
                     if let Some(&m) = middle_ft {
                         m
@@ -184,6 +186,6 @@ impl Sphere {
             sphere.indices = temp_indices;
         }
         sphere.calculate_normals();
-        sphere
+        Ok(sphere)
     }
 }
\ No newline at end of file
@@ -0,0 +1,185 @@
+use std::path::Path;
+use crate::shader::ShaderError;
+
+/// Directory `#include "file"` is resolved against.
+pub const SHADER_DIR: &str = "shaders";
+
+/// Preprocessed GLSL, with enough bookkeeping to point errors back at the original files.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub code: String,
+    /// Every file that went into `code`, starting with the root.
+    pub files: Vec<String>,
+    /// For each line of `code`, the index in `files` and the line number it came from.
+    lines: Vec<Option<(usize, u32)>>,
+}
+
+impl Source {
+    /// File and line a 1-based line of `code` came from. `None` for generated lines.
+    pub fn origin(&self, line: u32) -> Option<(&str, u32)> {
+        let (file, line) = (*self.lines.get((line as usize).checked_sub(1)?)?)?;
+        Some((&self.files[file], line))
+    }
+}
+
+/// Resolves `#include`s, drops the files' own `#version` lines and puts a single `#version` with
+/// `defines` at the top. Each file is included at most once. `load` reads the files named by
+/// includes, as paths under `SHADER_DIR`.
+///
+/// The version is the highest any of the files declares, capped at `max_version`, the GLSL
+/// version of the context, which is also used when no file declares one. The profile is
+/// `compatibility` if a file asks for it and `core` otherwise.
+pub fn preprocess(
+    path: &str,
+    code: &str,
+    defines: &[(String, String)],
+    max_version: u32,
+    load: &mut dyn FnMut(&str) -> Result<String, ShaderError>,
+) -> Result<Source, ShaderError> {
+    let mut body = Source {
+        code: String::new(),
+        files: Vec::new(),
+        lines: Vec::new(),
+    };
+    let mut versions = Vec::new();
+    body.include(path, code, &mut versions, load)?;
+
+    let version = versions.iter().map(|&(number, _)| number).max().map_or(max_version, |v| v.min(max_version));
+    let profile = if versions.iter().any(|&(_, compatibility)| compatibility) { "compatibility" } else { "core" };
+    let mut source = Source {
+        code: String::new(),
+        files: body.files,
+        lines: Vec::new(),
+    };
+    // Profiles only exist from GLSL 1.50.
+    if version >= 150 {
+        source.push(&format!("#version {} {}", version, profile), None);
+    } else {
+        source.push(&format!("#version {}", version), None);
+    }
+    for (name, value) in defines {
+        source.push(&format!("#define {} {}", name, value), None);
+    }
+    source.code.push_str(&body.code);
+    source.lines.extend(body.lines);
+    Ok(source)
+}
+
+impl Source {
+    fn push(&mut self, line: &str, origin: Option<(usize, u32)>) {
+        self.code.push_str(line);
+        self.code.push('\n');
+        self.lines.push(origin);
+    }
+
+    /// Appends `code`, with its includes resolved, and collects the declared versions and whether
+    /// they ask for the compatibility profile.
+    fn include(
+        &mut self,
+        path: &str,
+        code: &str,
+        versions: &mut Vec<(u32, bool)>,
+        load: &mut dyn FnMut(&str) -> Result<String, ShaderError>,
+    ) -> Result<(), ShaderError> {
+        let file = self.files.len();
+        self.files.push(path.to_string());
+        for (number, line) in (1..).zip(code.lines()) {
+            let directive = line.trim_start();
+            if let Some(argument) = directive.strip_prefix("#version") {
+                let mut words = argument.split_whitespace();
+                let Some(version) = words.next().and_then(|v| v.parse().ok()) else {
+                    return Err(ShaderError::Preprocess {
+                        path: path.to_string(),
+                        line: number,
+                        message: format!("Expected #version number, found `{}`.", directive),
+                    });
+                };
+                versions.push((version, words.next() == Some("compatibility")));
+                continue;
+            }
+            let Some(argument) = directive.strip_prefix("#include") else {
+                self.push(line, Some((file, number)));
+                continue;
+            };
+            let name = argument.trim().strip_prefix('"').and_then(|a| a.strip_suffix('"'));
+            let Some(name) = name.filter(|n| !n.is_empty()) else {
+                return Err(ShaderError::Preprocess {
+                    path: path.to_string(),
+                    line: number,
+                    message: format!("Expected #include \"file\", found `{}`.", directive),
+                });
+            };
+            let included = Path::new(SHADER_DIR).join(name).to_string_lossy().into_owned();
+            if !self.files.contains(&included) {
+                let code = load(&included)?;
+                self.include(&included, &code, versions, load)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(name: &str) -> Result<String, ShaderError> {
+        match name {
+            "shaders/light.glsl" => Ok("#version 460\n#include \"util.glsl\"\nvec3 light() { return one(); }".to_string()),
+            "shaders/util.glsl" => Ok("#include \"light.glsl\"\nvec3 one() { return vec3(1.0); }".to_string()),
+            _ => Err(ShaderError::Io { path: name.to_string(), error: std::io::ErrorKind::NotFound.into() }),
+        }
+    }
+
+    #[test]
+    fn includes_are_resolved_once() {
+        let code = "#version 330 core\n#include \"light.glsl\"\n#include \"util.glsl\"\nvoid main() {}";
+        let source = preprocess("shaders/main.frag", code, &[], 450, &mut files).unwrap();
+        assert_eq!(source.code, "#version 450 core\nvec3 one() { return vec3(1.0); }\nvec3 light() { return one(); }\nvoid main() {}\n");
+        assert_eq!(source.files, ["shaders/main.frag", "shaders/light.glsl", "shaders/util.glsl"]);
+    }
+
+    #[test]
+    fn defines_follow_version() {
+        let defines = [("SHADOWS".to_string(), "1".to_string()), ("LIGHTS".to_string(), "4".to_string())];
+        let source = preprocess("a.vert", "#version 460\nvoid main() {}", &defines, 460, &mut files).unwrap();
+        assert_eq!(source.code, "#version 460 core\n#define SHADOWS 1\n#define LIGHTS 4\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn version_is_the_highest_the_context_supports() {
+        let version = |code: &str, max_version| {
+            let source = preprocess("a.vert", code, &[], max_version, &mut files).unwrap();
+            source.code.lines().next().unwrap().to_string()
+        };
+        assert_eq!(version("#version 330 core\nvoid main() {}", 460), "#version 330 core");
+        assert_eq!(version("#version 330 core\n#include \"light.glsl\"", 460), "#version 460 core");
+        assert_eq!(version("#version 330 core\n#include \"light.glsl\"", 330), "#version 330 core");
+        assert_eq!(version("#version 400 compatibility\n", 450), "#version 400 compatibility");
+        assert_eq!(version("void main() {}", 450), "#version 450 core");
+        assert_eq!(version("#version 120\n", 450), "#version 120");
+        let e = preprocess("a.vert", "#version\n", &[], 450, &mut files).unwrap_err();
+        assert!(matches!(e, ShaderError::Preprocess { line: 1, .. }));
+    }
+
+    #[test]
+    fn lines_map_back_to_their_files() {
+        let code = "#version 460\n#include \"light.glsl\"\n\nvoid main() {}";
+        let source = preprocess("shaders/main.frag", code, &[("A".to_string(), "1".to_string())], 460, &mut files).unwrap();
+        assert_eq!(source.origin(1), None);
+        assert_eq!(source.origin(2), None);
+        assert_eq!(source.origin(3), Some(("shaders/util.glsl", 2)));
+        assert_eq!(source.origin(4), Some(("shaders/light.glsl", 3)));
+        assert_eq!(source.origin(6), Some(("shaders/main.frag", 4)));
+        assert_eq!(source.origin(7), None);
+        assert_eq!(source.origin(0), None);
+    }
+
+    #[test]
+    fn bad_includes_are_reported() {
+        let e = preprocess("a.frag", "\n#include <light.glsl>", &[], 460, &mut files).unwrap_err();
+        assert!(matches!(e, ShaderError::Preprocess { ref path, line: 2, .. } if path == "a.frag"));
+        let e = preprocess("a.frag", "#include \"missing.glsl\"", &[], 460, &mut files).unwrap_err();
+        assert!(matches!(e, ShaderError::Io { ref path, .. } if path == "shaders/missing.glsl"));
+    }
+}
@@ -0,0 +1,462 @@
+use std::cell::RefCell;
+use crate::frame;
+use crate::light::{Light, Lighting};
+use crate::math::{Camera, Mat4x4, Trs, Vec3};
+use crate::shadow::ShadowPass;
+use crate::state::RenderState;
+use crate::traits::{Drawable, Transform};
+
+/// Anything a scene node can hold.
+pub trait SceneObject: Drawable + Transform {}
+
+impl<T: Drawable + Transform> SceneObject for T {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// A named node. Its local transform is the held object's transform, or its own for empty
+/// nodes, and is relative to the parent node.
+pub struct Node {
+    name: String,
+    object: Option<Box<dyn SceneObject>>,
+    transform: Trs,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    pub fn object(&self) -> Option<&dyn SceneObject> {
+        self.object.as_deref()
+    }
+
+    pub fn object_mut(&mut self) -> Option<&mut (dyn SceneObject + 'static)> {
+        self.object.as_deref_mut()
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children[..]
+    }
+}
+
+impl Transform for Node {
+    fn get_trs(&self) -> &Trs {
+        match &self.object {
+            Some(object) => object.get_trs(),
+            None => &self.transform,
+        }
+    }
+
+    fn get_trs_mut(&mut self) -> &mut Trs {
+        match &mut self.object {
+            Some(object) => object.get_trs_mut(),
+            None => &mut self.transform,
+        }
+    }
+}
+
+/// Hierarchy of nodes whose children inherit the world transform of their parent.
+/// Lights added to the scene shine on every node.
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+    lights: Vec<Light>,
+    shadow_bounds: (Vec3, f32),
+    shadow_map_size: i32,
+    shadow_pass: RefCell<Option<ShadowPass>>,
+    /// Why shadows are off, after the shadow pass failed. Not retried until the settings change.
+    shadow_error: RefCell<Option<String>>,
+    render_state: RenderState,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            lights: Vec::new(),
+            shadow_bounds: (Vec3::zero(), 20.0),
+            shadow_map_size: 2048,
+            shadow_pass: RefCell::new(None),
+            shadow_error: RefCell::new(None),
+            render_state: RenderState::default(),
+        }
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<O: SceneObject + 'static>(&mut self, name: &str, object: O) -> NodeId {
+        self.insert(name, Some(Box::new(object)), None)
+    }
+
+    /// Adds a node without an object, useful as a pivot for its children.
+    pub fn add_empty(&mut self, name: &str) -> NodeId {
+        self.insert(name, None, None)
+    }
+
+    /// Fails if `parent` doesn't exist, e.g. because it was removed.
+    pub fn add_child<O: SceneObject + 'static>(&mut self, parent: NodeId, name: &str, object: O) -> Result<NodeId, String> {
+        self.insert_child(parent, name, Some(Box::new(object)))
+    }
+
+    /// Fails if `parent` doesn't exist, e.g. because it was removed.
+    pub fn add_empty_child(&mut self, parent: NodeId, name: &str) -> Result<NodeId, String> {
+        self.insert_child(parent, name, None)
+    }
+
+    fn insert_child(&mut self, parent: NodeId, name: &str, object: Option<Box<dyn SceneObject>>) -> Result<NodeId, String> {
+        if self.get(parent).is_none() {
+            return Err("Parent node doesn't exist.".to_string());
+        }
+        Ok(self.insert(name, object, Some(parent)))
+    }
+
+    /// `parent` must exist.
+    fn insert(&mut self, name: &str, object: Option<Box<dyn SceneObject>>, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(Node {
+            name: name.to_string(),
+            object,
+            transform: Trs::identity(),
+            parent,
+            children: Vec::new(),
+        }));
+        match parent {
+            Some(p) => self.node_mut(p).children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights[..]
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    /// Region directional lights cast shadows in: a sphere of `radius` around `center`.
+    pub fn set_shadow_bounds(&mut self, center: Vec3, radius: f32) {
+        self.shadow_bounds = (center, radius);
+    }
+
+    pub fn set_shadow_map_size(&mut self, size: i32) {
+        self.shadow_map_size = size;
+        self.shadow_pass.replace(None);
+        self.shadow_error.replace(None);
+    }
+
+    /// Why shadows are disabled, if the shadow pass failed. It is tried again after
+    /// `set_shadow_map_size`.
+    pub fn shadow_error(&self) -> Option<String> {
+        self.shadow_error.borrow().clone()
+    }
+
+    pub fn render_state(&self) -> &RenderState {
+        &self.render_state
+    }
+
+    /// State every node is drawn with, unless its object overrides parts of it.
+    pub fn set_render_state(&mut self, state: RenderState) {
+        self.render_state = state;
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0).and_then(Option::as_mut)
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.get_mut(id).unwrap()
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots[..]
+    }
+
+    /// First node named `name`, in depth-first order.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.get(id) else { continue; };
+            if node.name == name {
+                return Some(id);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        None
+    }
+
+    /// Moves `id` under `parent`, or to the top level for `None`. The node keeps its local
+    /// transform, so its world transform follows the new parent.
+    pub fn reparent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        if self.get(id).is_none() {
+            return Err("Node doesn't exist.".to_string());
+        }
+        if let Some(p) = parent {
+            if self.get(p).is_none() {
+                return Err("Parent node doesn't exist.".to_string());
+            }
+            if self.ancestors(p).any(|a| a == id) {
+                return Err("A node can't be moved under its own descendant.".to_string());
+            }
+        }
+
+        match self.node_mut(id).parent.take() {
+            Some(old) => self.node_mut(old).children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+        self.node_mut(id).parent = parent;
+        match parent {
+            Some(p) => self.node_mut(p).children.push(id),
+            None => self.roots.push(id),
+        }
+        Ok(())
+    }
+
+    /// Removes `id` and all of its descendants, returning the removed node.
+    pub fn remove(&mut self, id: NodeId) -> Option<Node> {
+        let mut node = self.nodes.get_mut(id.0)?.take()?;
+        match node.parent.and_then(|p| self.get_mut(p)) {
+            Some(parent) => parent.children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+        for child in std::mem::take(&mut node.children) {
+            self.remove(child);
+        }
+        Some(node)
+    }
+
+    /// The node itself followed by its parent, grandparent and so on.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(Some(id), |&i| self.get(i).and_then(|n| n.parent))
+    }
+
+    pub fn world_matrix(&self, id: NodeId) -> Mat4x4 {
+        self.ancestors(id)
+            .filter_map(|i| self.get(i))
+            .fold(Mat4x4::identity(), |m, node| &node.get_matrix() * &m)
+    }
+
+    /// Visits every node depth-first, parents before children, with its world matrix.
+    pub fn traverse<F: FnMut(NodeId, &Node, &Mat4x4)>(&self, mut f: F) {
+        for &root in &self.roots {
+            self.visit(root, &Mat4x4::identity(), &mut f);
+        }
+    }
+
+    fn visit<F: FnMut(NodeId, &Node, &Mat4x4)>(&self, id: NodeId, parent: &Mat4x4, f: &mut F) {
+        let Some(node) = self.get(id) else { return; };
+        let world = parent * &node.get_matrix();
+        f(id, node, &world);
+        for &child in &node.children {
+            self.visit(child, &world, f);
+        }
+    }
+
+    /// Renders the shadow maps of shadow-casting lights, then draws every node.
+    /// If the shadow pass can't be set up, the error is reported and the scene is drawn without shadows.
+    pub fn draw(&self, camera: &Camera) {
+        let mut shadow_pass = self.shadow_pass.borrow_mut();
+        let mut failure = None;
+        if !self.lights.iter().any(|l| l.casts_shadows) || self.shadow_error.borrow().is_some() {
+            *shadow_pass = None;
+        } else if shadow_pass.is_none() {
+            match ShadowPass::new(self.shadow_map_size) {
+                Ok(pass) => *shadow_pass = Some(pass),
+                Err(e) => failure = Some(e.to_string()),
+            }
+        }
+        if let Some(pass) = shadow_pass.as_mut() {
+            let (center, radius) = self.shadow_bounds;
+            let result = pass.render(&self.lights, center, radius, |light_space, program| {
+                self.traverse(|_, node, world| {
+                    if let Some(object) = node.object() {
+                        object.draw_shadow(world, light_space, program);
+                    }
+                });
+            });
+            if let Err(e) = result {
+                failure = Some(e);
+                *shadow_pass = None;
+            }
+        }
+        // Reported once: shadows stay off instead of failing again every frame.
+        if let Some(error) = failure {
+            eprintln!("Shadows disabled: {}", error);
+            self.shadow_error.replace(Some(error));
+        }
+
+        frame::begin(camera);
+        let lighting = Lighting {
+            lights: &self.lights,
+            shadow_maps: shadow_pass.as_ref().map_or(&[], |p| p.maps()),
+        };
+        self.traverse(|_, node, world| {
+            if let Some(object) = node.object() {
+                object.draw_with(world, &lighting, &self.render_state);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+    use crate::headless::test_context;
+    use crate::shader::Program;
+    use crate::traits::{Positioned, Rotated};
+
+    struct Probe {
+        transform: Trs,
+        drawn: Rc<RefCell<Vec<Vec3>>>,
+    }
+
+    impl Probe {
+        fn new(drawn: &Rc<RefCell<Vec<Vec3>>>) -> Self {
+            Self { transform: Trs::identity(), drawn: drawn.clone() }
+        }
+    }
+
+    impl Transform for Probe {
+        fn get_trs(&self) -> &Trs {
+            &self.transform
+        }
+        fn get_trs_mut(&mut self) -> &mut Trs {
+            &mut self.transform
+        }
+    }
+
+    impl Drawable for Probe {
+        fn draw(&self, _camera: &Camera) {
+            self.draw_with(&self.get_matrix(), &Lighting::default(), &RenderState::default());
+        }
+        fn draw_with(&self, model: &Mat4x4, _lighting: &Lighting, _state: &RenderState) {
+            self.drawn.borrow_mut().push(model.transform_point(Vec3::zero()));
+        }
+        fn draw_shadow(&self, _model: &Mat4x4, _light_space: &Mat4x4, _program: &Program) {}
+    }
+
+    fn assert_vec_close(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn children_inherit_parent_transform() {
+        let drawn = Rc::new(RefCell::new(Vec::new()));
+        let mut scene = Scene::new();
+        let planet = scene.add("planet", Probe::new(&drawn));
+        let moon = scene.add_child(planet, "moon", Probe::new(&drawn)).unwrap();
+        scene.get_mut(planet).unwrap().translate_by(Vec3(10.0, 0.0, 0.0));
+        scene.get_mut(moon).unwrap().translate_by(Vec3(2.0, 0.0, 0.0));
+
+        scene.get_mut(planet).unwrap().rotate_around(90.0, Vec3::up());
+        assert_vec_close(scene.world_matrix(moon).transform_point(Vec3::zero()), Vec3(10.0, 0.0, -2.0));
+
+        // `draw` hands each object the world matrix `traverse` computes.
+        let mut origins = Vec::new();
+        scene.traverse(|_, _, world| origins.push(world.transform_point(Vec3::zero())));
+        assert_eq!(origins.len(), 2);
+        assert_vec_close(origins[0], Vec3(10.0, 0.0, 0.0));
+        assert_vec_close(origins[1], Vec3(10.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn find_and_reparent() {
+        let drawn = Rc::new(RefCell::new(Vec::new()));
+        let mut scene = Scene::new();
+        let pivot = scene.add_empty("pivot");
+        let a = scene.add("a", Probe::new(&drawn));
+        let b = scene.add_child(a, "b", Probe::new(&drawn)).unwrap();
+        assert_eq!(scene.find("b"), Some(b));
+        assert_eq!(scene.find("missing"), None);
+        // Depth-first: a's child comes before the later root of the same name.
+        let later = scene.add_empty("b");
+        assert_eq!(scene.find("b"), Some(b));
+        scene.remove(later);
+
+        scene.get_mut(pivot).unwrap().translate_by(Vec3(0.0, 5.0, 0.0));
+        scene.reparent(b, Some(pivot)).unwrap();
+        assert_eq!(scene.get(b).unwrap().parent(), Some(pivot));
+        assert!(scene.get(a).unwrap().children().is_empty());
+        assert_vec_close(scene.world_matrix(b).transform_point(Vec3::zero()), Vec3(0.0, 5.0, 0.0));
+
+        scene.reparent(pivot, Some(a)).unwrap();
+        assert!(scene.reparent(a, Some(b)).is_err());
+        assert!(scene.reparent(a, Some(a)).is_err());
+        assert_eq!(scene.roots(), &[a]);
+
+        scene.reparent(b, None).unwrap();
+        assert_eq!(scene.roots(), &[a, b]);
+    }
+
+    #[test]
+    fn remove_drops_subtree() {
+        let drawn = Rc::new(RefCell::new(Vec::new()));
+        let mut scene = Scene::new();
+        let a = scene.add("a", Probe::new(&drawn));
+        let b = scene.add_child(a, "b", Probe::new(&drawn)).unwrap();
+        let c = scene.add_child(b, "c", Probe::new(&drawn)).unwrap();
+        assert_eq!(scene.remove(b).unwrap().name(), "b");
+        assert!(scene.get(c).is_none());
+        assert!(scene.get(a).unwrap().children().is_empty());
+        assert!(scene.remove(b).is_none());
+
+        // Children of a removed node are rejected instead of becoming roots.
+        assert!(scene.add_child(b, "d", Probe::new(&drawn)).is_err());
+        assert!(scene.add_empty_child(c, "e").is_err());
+        assert_eq!(scene.roots(), &[a]);
+    }
+
+    #[test]
+    fn shadow_failures_are_kept() {
+        let _headless = test_context(16, 16);
+        let drawn = Rc::new(RefCell::new(Vec::new()));
+        let mut scene = Scene::new();
+        scene.add("probe", Probe::new(&drawn));
+        scene.add_light(Light::directional(Vec3(0.0, -1.0, 0.0), Vec3(1.0, 1.0, 1.0), 1.0).with_shadows());
+        let camera = Camera::orthographic(-1.0, 1.0, -1.0, 1.0, 0.1, 10.0);
+
+        // An empty map can't be rendered into, so shadows turn off and stay off.
+        scene.set_shadow_map_size(0);
+        scene.draw(&camera);
+        let error = scene.shadow_error().expect("An empty shadow map should fail");
+        assert!(scene.shadow_pass.borrow().is_none());
+        scene.draw(&camera);
+        assert_eq!(scene.shadow_error(), Some(error));
+        assert!(scene.shadow_pass.borrow().is_none());
+        assert_eq!(drawn.borrow().len(), 2);
+
+        // A new size gives shadows another try.
+        scene.set_shadow_map_size(64);
+        scene.draw(&camera);
+        assert_eq!(scene.shadow_error(), None);
+        assert_eq!(scene.shadow_pass.borrow().as_ref().map(|p| p.maps().len()), Some(1));
+    }
+}
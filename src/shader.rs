@@ -0,0 +1,530 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::{CString, NulError};
+use std::fmt;
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use crate::gl;
+use crate::preprocess::{self, Source};
+use crate::uniform::Uniform;
+
+/// Vertex shader used when an object doesn't name one.
+pub const DEFAULT_VERTEX_SHADER: &str = "#version 460 core
+layout(location = 0) in vec3 vPos;
+layout(location = 1) in vec3 nPos;
+
+#include \"common.vert\"
+
+uniform mat4 model;
+uniform mat4 normalMatrix;
+
+out vec3 worldPosition;
+out vec3 worldNormal;
+
+void main() {
+    vec4 world = model * vec4(vPos, 1.0);
+    gl_Position = projection * view * world;
+    worldPosition = world.xyz;
+    worldNormal = mat3(normalMatrix) * nPos;
+}";
+
+/// Fragment shader used when an object doesn't name one.
+pub const DEFAULT_FRAGMENT_SHADER: &str = "#version 460 core
+in vec3 worldPosition;
+in vec3 worldNormal;
+
+uniform vec4 color;
+
+out vec4 fragColor;
+
+#include \"common.frag\"
+
+void main() {
+    fragColor = vec4(blinnPhong(worldPosition, worldNormal, color.rgb), color.a);
+}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    fn gl_type(self) -> gl::types::GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io { path: String, error: std::io::Error },
+    /// `line` is the first error line reported by the driver, if its log could be parsed.
+    Compile { stage: ShaderStage, path: String, line: Option<u32>, log: String },
+    Link { log: String },
+    /// A malformed preprocessor directive.
+    Preprocess { path: String, line: u32, message: String },
+    Nul(NulError),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => write!(f, "Couldn't read {}: {}", path, error),
+            ShaderError::Compile { stage, path, line: Some(line), log } => {
+                write!(f, "{}:{}: {} shader failed to compile:\n{}", path, line, stage, log)
+            }
+            ShaderError::Compile { stage, path, line: None, log } => {
+                write!(f, "{}: {} shader failed to compile:\n{}", path, stage, log)
+            }
+            ShaderError::Link { log } => write!(f, "Shader program failed to link:\n{}", log),
+            ShaderError::Preprocess { path, line, message } => write!(f, "{}:{}: {}", path, line, message),
+            ShaderError::Nul(e) => write!(f, "Shader source contains a nul byte: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShaderError::Io { error, .. } => Some(error),
+            ShaderError::Nul(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<NulError> for ShaderError {
+    fn from(e: NulError) -> Self {
+        ShaderError::Nul(e)
+    }
+}
+
+/// Line number of the first error in a driver info log. Understands the Mesa (`0:12(5):`),
+/// AMD (`ERROR: 0:12:`) and NVIDIA (`0(12) :`) formats.
+pub fn error_line(log: &str) -> Option<u32> {
+    let parse = |line: &str| -> Option<u32> {
+        let line = line.trim_start();
+        let line = line.strip_prefix("ERROR: ").unwrap_or(line);
+        let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let n = digits(line);
+        if n == 0 { return None; }
+        let separator = line[n..].chars().next()?;
+        let rest = &line[n + 1..];
+        let m = digits(rest);
+        if m == 0 { return None; }
+        match (separator, rest[m..].chars().next()) {
+            (':', Some('(' | ':')) | ('(', Some(')')) => rest[..m].parse().ok(),
+            _ => None,
+        }
+    };
+    log.lines()
+        .filter(|l| l.to_lowercase().contains("error"))
+        .find_map(parse)
+        .or_else(|| log.lines().find_map(parse))
+}
+
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+
+/// Whether the shaders were compiled into the binary, in which case they are never read from disk.
+pub const EMBEDDED: bool = cfg!(feature = "embedded-shaders");
+
+/// Reads `path`, preferring the copy embedded at build time. `None` gives the built-in `default`.
+fn read(path: Option<&str>, default: &str) -> Result<(String, String), ShaderError> {
+    let embedded = path.and_then(|path| embedded::SHADERS.iter().find(|(name, _)| Path::new(name) == Path::new(path)));
+    if let Some((name, code)) = embedded {
+        return Ok((name.to_string(), code.to_string()));
+    }
+    match path {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(code) => Ok((path.to_string(), code)),
+            Err(error) => Err(ShaderError::Io { path: path.to_string(), error }),
+        },
+        None => Ok(("<built-in>".to_string(), default.to_string())),
+    }
+}
+
+unsafe fn shader_log(id: u32) -> String {
+    let mut length = 0;
+    gl::GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut length);
+    let mut log = vec![0u8; length.max(1) as usize];
+    gl::GetShaderInfoLog(id, length, core::ptr::null_mut(), log.as_mut_ptr() as *mut gl::types::GLchar);
+    String::from_utf8_lossy(&log).trim_end_matches('\0').trim_end().to_string()
+}
+
+unsafe fn program_log(id: u32) -> String {
+    let mut length = 0;
+    gl::GetProgramiv(id, gl::INFO_LOG_LENGTH, &mut length);
+    let mut log = vec![0u8; length.max(1) as usize];
+    gl::GetProgramInfoLog(id, length, core::ptr::null_mut(), log.as_mut_ptr() as *mut gl::types::GLchar);
+    String::from_utf8_lossy(&log).trim_end_matches('\0').trim_end().to_string()
+}
+
+unsafe fn compile(stage: ShaderStage, source: &Source) -> Result<u32, ShaderError> {
+    let cstring = CString::new(source.code.as_str())?;
+    let id = gl::CreateShader(stage.gl_type());
+    let pointer = cstring.as_ptr();
+    gl::ShaderSource(id, 1, &pointer, core::ptr::null());
+    gl::CompileShader(id);
+
+    let mut result = 0;
+    gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut result);
+    if result != gl::TRUE as i32 {
+        let log = shader_log(id);
+        gl::DeleteShader(id);
+        // Point at the file the offending line was included from rather than the combined source.
+        let (path, line) = match error_line(&log).and_then(|line| source.origin(line)) {
+            Some((path, line)) => (path.to_string(), Some(line)),
+            None => (source.files[0].clone(), None),
+        };
+        return Err(ShaderError::Compile { stage, path, line, log });
+    }
+    if cfg!(debug_assertions) {
+        let log = shader_log(id);
+        if !log.is_empty() {
+            eprintln!("{}: {}", source.files[0], log);
+        }
+    }
+    Ok(id)
+}
+
+/// GLSL version of the current context, e.g. 450 for OpenGL 4.5. From OpenGL 3.3 on the GLSL
+/// version follows the GL version.
+fn context_glsl_version() -> u32 {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major * 100 + minor * 10) as u32
+}
+
+/// Preprocesses and compiles `vertex` and `fragment` (or the built-in shaders for `None`) and
+/// links them into a new program. `defines` are added to every stage. Also returns every file
+/// the program was built from.
+fn build(vertex: Option<&str>, fragment: Option<&str>, defines: &[(String, String)]) -> Result<(u32, Vec<String>), ShaderError> {
+    let mut load = |path: &str| read(Some(path), "").map(|(_, code)| code);
+    let max_version = context_glsl_version();
+    let mut sources = Vec::new();
+    for (stage, path, default) in [
+        (ShaderStage::Vertex, vertex, DEFAULT_VERTEX_SHADER),
+        (ShaderStage::Fragment, fragment, DEFAULT_FRAGMENT_SHADER),
+    ] {
+        let (path, code) = read(path, default)?;
+        sources.push((stage, preprocess::preprocess(&path, &code, defines, max_version, &mut load)?));
+    }
+    let files = sources.iter().flat_map(|(_, source)| source.files.iter().cloned()).collect();
+
+    unsafe {
+        let mut shaders = Vec::new();
+        for (stage, source) in &sources {
+            match compile(*stage, source) {
+                Ok(id) => shaders.push(id),
+                Err(e) => {
+                    shaders.iter().for_each(|&id| gl::DeleteShader(id));
+                    return Err(e);
+                }
+            }
+        }
+
+        let program_id = gl::CreateProgram();
+        shaders.iter().for_each(|&id| gl::AttachShader(program_id, id));
+        gl::LinkProgram(program_id);
+        shaders.iter().for_each(|&id| {
+            gl::DetachShader(program_id, id);
+            gl::DeleteShader(id);
+        });
+
+        let mut result = 0;
+        gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut result);
+        if result != gl::TRUE as i32 {
+            let log = program_log(program_id);
+            gl::DeleteProgram(program_id);
+            return Err(ShaderError::Link { log });
+        }
+        Ok((program_id, files))
+    }
+}
+
+/// A linked GL program, deleted when dropped. Reloading swaps the GL program in place, so the
+/// id can change between frames.
+#[derive(Debug)]
+pub struct Program {
+    id: Cell<u32>,
+    files: RefCell<Vec<String>>,
+    /// Uniform locations already queried, -1 for names that aren't active.
+    locations: RefCell<HashMap<String, i32>>,
+}
+
+impl Program {
+    fn new(id: u32, files: Vec<String>) -> Self {
+        Self {
+            id: Cell::new(id),
+            files: RefCell::new(files),
+            locations: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id.get()
+    }
+
+    /// Location of the uniform `name`, or -1 if the program has no such active uniform.
+    /// Debug builds warn the first time an unknown name is looked up.
+    pub fn location(&self, name: &str) -> i32 {
+        self.lookup(name, cfg!(debug_assertions))
+    }
+
+    fn lookup(&self, name: &str, warn: bool) -> i32 {
+        if let Some(&location) = self.locations.borrow().get(name) {
+            return location;
+        }
+        let location = match CString::new(name) {
+            Ok(cstring) => unsafe { gl::GetUniformLocation(self.id(), cstring.as_ptr()) },
+            Err(_) => -1,
+        };
+        if location < 0 && warn {
+            eprintln!("Warning: {} has no active uniform `{}`.", self.files.borrow().join(", "), name);
+        }
+        self.locations.borrow_mut().insert(name.to_string(), location);
+        location
+    }
+
+    /// Sets the uniform `name`. The program doesn't need to be in use.
+    pub fn set_uniform(&self, name: &str, value: impl Uniform) {
+        let location = self.location(name);
+        if location >= 0 {
+            unsafe { value.upload(self.id(), location) };
+        }
+    }
+
+    /// Sets one of the uniforms the renderer provides to every program. Shaders are free to
+    /// ignore those, so unused ones don't warn.
+    pub(crate) fn set_builtin_uniform(&self, name: &str, value: impl Uniform) {
+        let location = self.lookup(name, false);
+        if location >= 0 {
+            unsafe { value.upload(self.id(), location) };
+        }
+    }
+
+    /// Whether the last successful build of this program read `path`.
+    pub fn depends_on(&self, path: &Path) -> bool {
+        self.files.borrow().iter().any(|file| same_file(Path::new(file), path))
+    }
+
+    fn replace(&self, id: u32, files: Vec<String>) {
+        unsafe {
+            gl::DeleteProgram(self.id.replace(id));
+        }
+        *self.files.borrow_mut() = files;
+        self.locations.borrow_mut().clear();
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id.get());
+        }
+    }
+}
+
+/// What a program is built from: shader paths (`None` for the built-in ones) and defines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShaderKey {
+    pub vertex: Option<String>,
+    pub fragment: Option<String>,
+    pub defines: Vec<(String, String)>,
+}
+
+impl ShaderKey {
+    pub fn new(vertex: Option<&str>, fragment: Option<&str>, defines: &[(&str, &str)]) -> Self {
+        Self {
+            vertex: vertex.map(str::to_string),
+            fragment: fragment.map(str::to_string),
+            defines: defines.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+        }
+    }
+}
+
+/// Programs shared between everything built from the same `ShaderKey`. Only weak references
+/// are kept, so a program is deleted once its last user drops it.
+#[derive(Default)]
+pub struct ShaderCache {
+    programs: HashMap<ShaderKey, Weak<Program>>,
+}
+
+thread_local! {
+    // GL contexts are bound to a thread, and so are the programs created in them.
+    static SHADER_CACHE: RefCell<ShaderCache> = RefCell::new(ShaderCache::default());
+}
+
+impl ShaderCache {
+    /// Runs `f` with this thread's cache.
+    pub fn with<R>(f: impl FnOnce(&mut ShaderCache) -> R) -> R {
+        SHADER_CACHE.with(|cache| f(&mut cache.borrow_mut()))
+    }
+
+    pub fn get(&mut self, key: &ShaderKey) -> Result<Rc<Program>, ShaderError> {
+        if let Some(program) = self.programs.get(key).and_then(Weak::upgrade) {
+            return Ok(program);
+        }
+        let (id, files) = build(key.vertex.as_deref(), key.fragment.as_deref(), &key.defines)?;
+        let program = Rc::new(Program::new(id, files));
+        self.programs.retain(|_, p| p.strong_count() > 0);
+        self.programs.insert(key.clone(), Rc::downgrade(&program));
+        Ok(program)
+    }
+
+    /// Rebuilds every live program reading one of `changed`. A program that fails to build keeps
+    /// its last good version; the errors are returned so the caller can report them.
+    pub fn reload(&mut self, changed: &[impl AsRef<Path>]) -> Vec<ShaderError> {
+        let mut errors = Vec::new();
+        for (key, program) in &self.programs {
+            let Some(program) = program.upgrade() else { continue };
+            if !changed.iter().any(|path| program.depends_on(path.as_ref())) {
+                continue;
+            }
+            match build(key.vertex.as_deref(), key.fragment.as_deref(), &key.defines) {
+                Ok((id, files)) => program.replace(id, files),
+                Err(error) => errors.push(error),
+            }
+        }
+        errors
+    }
+
+    /// Number of programs still in use.
+    pub fn len(&self) -> usize {
+        self.programs.values().filter(|p| p.strong_count() > 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::test_context;
+
+    #[test]
+    fn error_line_formats() {
+        assert_eq!(error_line("0:12(5): error: `foo' undeclared"), Some(12));
+        assert_eq!(error_line("ERROR: 0:7: 'bar' : undeclared identifier"), Some(7));
+        assert_eq!(error_line("0(31) : error C1008: undefined variable \"baz\""), Some(31));
+        assert_eq!(error_line("garbage"), None);
+        assert_eq!(error_line(""), None);
+    }
+
+    #[test]
+    fn error_line_prefers_errors_over_warnings() {
+        let log = "0:3(1): warning: unused variable\n0:9(2): error: syntax error";
+        assert_eq!(error_line(log), Some(9));
+        assert_eq!(error_line("0:3(1): warning: unused variable"), Some(3));
+    }
+
+    #[test]
+    fn keys_distinguish_defines() {
+        let plain = ShaderKey::new(Some("a.vert"), None, &[]);
+        let variant = ShaderKey::new(Some("a.vert"), None, &[("SHADOWS", "1")]);
+        assert_ne!(plain, variant);
+        assert_eq!(plain, ShaderKey::new(Some("a.vert"), None, &[]));
+    }
+
+    #[test]
+    fn programs_depend_on_their_sources() {
+        let _headless = test_context(16, 16);
+        let mut cache = ShaderCache::default();
+        let galaxy = cache.get(&ShaderKey::new(Some("shaders/galaxy.vert"), Some("shaders/galaxy.frag"), &[])).unwrap();
+        assert!(galaxy.depends_on(Path::new("shaders/galaxy.frag")));
+        assert!(galaxy.depends_on(Path::new("shaders/common.frag")));
+        assert!(galaxy.depends_on(Path::new("shaders/frame.glsl")));
+        assert!(galaxy.depends_on(&Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/common.vert")));
+        assert!(!galaxy.depends_on(Path::new("shaders/plain.frag")));
+        // The built-in shaders include the common ones too.
+        let builtin = cache.get(&ShaderKey::new(None, None, &[])).unwrap();
+        assert!(builtin.depends_on(Path::new("shaders/common.frag")));
+        assert!(!builtin.depends_on(Path::new("shaders/galaxy.frag")));
+    }
+
+    #[test]
+    fn reload_skips_unchanged_programs() {
+        let _headless = test_context(16, 16);
+        let mut cache = ShaderCache::default();
+        let galaxy = cache.get(&ShaderKey::new(Some("shaders/galaxy.vert"), Some("shaders/galaxy.frag"), &[])).unwrap();
+        let plain = cache.get(&ShaderKey::new(Some("shaders/plain.vert"), Some("shaders/plain.frag"), &[])).unwrap();
+        let ids = || (galaxy.id(), plain.id());
+        let before = ids();
+
+        assert!(cache.reload(&["shaders/depth.frag"]).is_empty());
+        assert_eq!(ids(), before);
+
+        assert!(cache.reload(&["shaders/galaxy.frag"]).is_empty());
+        assert_ne!(galaxy.id(), before.0);
+        assert_eq!(plain.id(), before.1);
+
+        let before = ids();
+        assert!(cache.reload(&["shaders/common.frag"]).is_empty());
+        assert_ne!(galaxy.id(), before.0);
+        assert_ne!(plain.id(), before.1);
+    }
+
+    #[test]
+    fn locations_are_cached_until_reload() {
+        let _headless = test_context(16, 16);
+        let mut cache = ShaderCache::default();
+        let plain = cache.get(&ShaderKey::new(Some("shaders/plain.vert"), Some("shaders/plain.frag"), &[])).unwrap();
+        let color = plain.location("color");
+        assert!(color >= 0);
+        assert_eq!(plain.location("missing"), -1);
+        assert_eq!(plain.locations.borrow().get("color"), Some(&color));
+        assert_eq!(plain.locations.borrow().get("missing"), Some(&-1));
+
+        // A rebuilt program may lay its uniforms out differently.
+        assert!(cache.reload(&["shaders/plain.frag"]).is_empty());
+        assert!(plain.locations.borrow().is_empty());
+        assert!(plain.location("color") >= 0);
+    }
+
+    #[test]
+    fn display_includes_location() {
+        let e = ShaderError::Compile {
+            stage: ShaderStage::Fragment,
+            path: "shaders/galaxy.frag".to_string(),
+            line: Some(4),
+            log: "0:4(1): error: oops".to_string(),
+        };
+        assert!(e.to_string().starts_with("shaders/galaxy.frag:4: fragment shader failed to compile"));
+        let e = read(Some("shaders/missing.vert"), "").unwrap_err();
+        assert!(matches!(e, ShaderError::Io { ref path, .. } if path == "shaders/missing.vert"));
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-shaders")]
+    fn embedded_shaders_match_the_files() {
+        assert!(!embedded::SHADERS.is_empty());
+        for (name, code) in embedded::SHADERS {
+            let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+            assert_eq!(std::fs::read_to_string(manifest.join(name)).unwrap(), *code);
+            assert_eq!(read(Some(name), "").unwrap().1, *code);
+        }
+    }
+}
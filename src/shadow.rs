@@ -0,0 +1,299 @@
+use std::rc::Rc;
+use crate::gl;
+use crate::light::{Light, LightKind};
+use crate::math::{Camera, Mat4x4, Radians, Vec3};
+use crate::shader::{Program, ShaderError};
+use crate::state::Viewport;
+use crate::traits::Shaded;
+use crate::uniform::{Texture, Uniforms};
+
+/// Number of shadow-casting lights, matching `MAX_SHADOW_MAPS` in `common.frag`.
+pub const MAX_SHADOW_MAPS: usize = 4;
+
+/// First texture unit used for shadow maps, leaving the lower units to the objects' own textures.
+pub const SHADOW_TEXTURE_UNIT: u32 = 8;
+
+/// Depth texture rendered from the point of view of one light.
+pub struct ShadowMap {
+    framebuffer: u32,
+    texture: u32,
+    size: i32,
+    light: usize,
+    light_space: Mat4x4,
+}
+
+impl ShadowMap {
+    pub fn new(size: i32) -> Result<Self, String> {
+        unsafe {
+            let mut texture = 0;
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH_COMPONENT32F as _,
+                size,
+                size,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                core::ptr::null(),
+            );
+            // Linear filtering on a comparison sampler gives an extra hardware PCF tap.
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as _);
+            gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, [1.0f32; 4].as_ptr());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as _);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            let mut previous = 0;
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut previous);
+            let mut framebuffer = 0;
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, texture, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous as u32);
+
+            let map = Self {
+                framebuffer,
+                texture,
+                size,
+                light: 0,
+                light_space: Mat4x4::identity(),
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Shadow map framebuffer is incomplete (status 0x{:x}).", status));
+            }
+            Ok(map)
+        }
+    }
+
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    pub fn texture(&self) -> u32 {
+        self.texture
+    }
+
+    /// Index of the light this map was last rendered for.
+    pub fn light(&self) -> usize {
+        self.light
+    }
+
+    pub fn light_space(&self) -> &Mat4x4 {
+        &self.light_space
+    }
+
+    /// Aims the map at `light`, see `light_space`. Returns false for point lights.
+    pub fn aim(&mut self, index: usize, light: &Light, center: Vec3, radius: f32) -> bool {
+        let Some(light_space) = light_space(light, center, radius) else { return false };
+        self.light = index;
+        self.light_space = light_space;
+        true
+    }
+
+    /// Binds the map as the render target, returning the state to restore afterwards.
+    unsafe fn begin(&self) -> (i32, Viewport, bool) {
+        let mut framebuffer = 0;
+        gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer);
+        let viewport = Viewport::current();
+        let depth_test = gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE;
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        Viewport::new(self.size, self.size).apply();
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthMask(gl::TRUE);
+        gl::Clear(gl::DEPTH_BUFFER_BIT);
+        gl::Enable(gl::POLYGON_OFFSET_FILL);
+        gl::PolygonOffset(2.0, 4.0);
+        (framebuffer, viewport, depth_test)
+    }
+
+    unsafe fn end(&self, (framebuffer, viewport, depth_test): (i32, Viewport, bool)) {
+        gl::Disable(gl::POLYGON_OFFSET_FILL);
+        if !depth_test {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer as u32);
+        viewport.apply();
+    }
+}
+
+/// Projection times view from `light`'s point of view. Directional lights cover a sphere of
+/// `radius` around `center`; spot lights cover their cone up to `radius` away. Point lights have
+/// no single direction and can't be mapped, so they give `None`.
+pub fn light_space(light: &Light, center: Vec3, radius: f32) -> Option<Mat4x4> {
+    let direction = light.direction.normalized();
+    let up = if direction.cross(Vec3::up()).len() < 1e-3 { Vec3::front() } else { Vec3::up() };
+    let (mut camera, eye, target) = match light.kind {
+        LightKind::Point => return None,
+        LightKind::Directional => (
+            Camera::orthographic(-radius, radius, -radius, radius, 0.0, 2.0 * radius),
+            center - radius * direction,
+            center,
+        ),
+        LightKind::Spot { outer, .. } => (
+            Camera::new(1.0, Radians((2.0 * Radians::from(outer).0).min(std::f32::consts::PI - 0.01)), 0.05, radius),
+            light.position,
+            light.position + direction,
+        ),
+    };
+    camera.look_at(eye, target, up);
+    Some(&camera.projection * &camera.view)
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/// Depth-only program and the shadow maps it renders into.
+pub struct ShadowPass {
+    program: Option<Rc<Program>>,
+    /// Written to the depth program before every map.
+    uniforms: Uniforms,
+    maps: Vec<ShadowMap>,
+    size: i32,
+}
+
+impl Shaded for ShadowPass {
+    fn get_program(&self) -> Option<&Rc<Program>> {
+        self.program.as_ref()
+    }
+    fn set_shader_program(&mut self, program: Rc<Program>) {
+        self.program = Some(program);
+    }
+    fn get_uniforms(&self) -> &Uniforms {
+        &self.uniforms
+    }
+    fn get_uniforms_mut(&mut self) -> &mut Uniforms {
+        &mut self.uniforms
+    }
+}
+
+impl ShadowPass {
+    pub fn new(size: i32) -> Result<Self, ShaderError> {
+        let mut pass = Self {
+            program: None,
+            uniforms: Uniforms::default(),
+            maps: Vec::new(),
+            size,
+        };
+        pass.compile_shaders(Some("shaders/depth.vert"), Some("shaders/depth.frag"))?;
+        Ok(pass)
+    }
+
+    pub fn maps(&self) -> &[ShadowMap] {
+        &self.maps[..]
+    }
+
+    /// Renders a shadow map for every shadow-casting light, up to `MAX_SHADOW_MAPS`.
+    /// `draw_casters` is called once per map and should draw every shadow caster.
+    pub fn render<F: FnMut(&Mat4x4, &Program)>(&mut self, lights: &[Light], center: Vec3, radius: f32, mut draw_casters: F) -> Result<(), String> {
+        let casters = lights.iter()
+            .enumerate()
+            .filter(|(_, l)| l.casts_shadows && l.kind != LightKind::Point)
+            .take(MAX_SHADOW_MAPS)
+            .collect::<Vec<_>>();
+        self.maps.truncate(casters.len());
+        while self.maps.len() < casters.len() {
+            self.maps.push(ShadowMap::new(self.size)?);
+        }
+        let Some(program) = self.program.as_ref() else { return Ok(()) };
+        for (map, (index, light)) in self.maps.iter_mut().zip(casters) {
+            map.aim(index, light, center, radius);
+            self.uniforms.upload(program);
+            unsafe {
+                let restore = map.begin();
+                draw_casters(map.light_space(), program);
+                map.end(restore);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Binds `maps` and sets the shadow uniforms of `program`.
+pub(crate) fn upload(program: &Program, maps: &[ShadowMap], receives: bool) {
+    // Every sampler gets its own unit even when unused, so they never alias other samplers.
+    let textures = (0..MAX_SHADOW_MAPS as u32)
+        .map(|i| Texture { id: maps.get(i as usize).map_or(0, |m| m.texture), unit: SHADOW_TEXTURE_UNIT + i })
+        .collect::<Vec<_>>();
+    program.set_builtin_uniform("shadowMaps", textures);
+    for (i, map) in maps.iter().enumerate() {
+        program.set_builtin_uniform(&format!("lightSpace[{}]", i), &map.light_space);
+    }
+    program.set_builtin_uniform("receiveShadows", receives);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::test_context;
+    use crate::math::Degrees;
+
+    const WHITE: Vec3 = Vec3(1.0, 1.0, 1.0);
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn directional_light_space_covers_the_bounds() {
+        let light = Light::directional(Vec3(0.0, -1.0, 0.0), WHITE, 1.0);
+        let m = light_space(&light, Vec3(1.0, 0.0, 0.0), 5.0).unwrap();
+        let center = m.transform_point(Vec3(1.0, 0.0, 0.0));
+        assert!(center.len() < 1e-4, "{:?}", center);
+        // The top of the bounds is nearest to the light, the bottom farthest.
+        assert_close(m.transform_point(Vec3(1.0, 5.0, 0.0)).z(), -1.0);
+        assert_close(m.transform_point(Vec3(1.0, -5.0, 0.0)).z(), 1.0);
+        let side = m.transform_point(Vec3(6.0, 0.0, 0.0));
+        assert_close(side.x().abs().max(side.y().abs()), 1.0);
+
+        assert!(light_space(&Light::point(Vec3::zero(), WHITE, 1.0), Vec3::zero(), 5.0).is_none());
+    }
+
+    #[test]
+    fn spot_light_space_fits_the_cone() {
+        let light = Light::spot(Vec3(0.0, 4.0, 0.0), Vec3(0.0, -1.0, 0.0), Degrees(20.0), Degrees(30.0), WHITE, 1.0);
+        let m = light_space(&light, Vec3::zero(), 10.0).unwrap();
+        let below = m.transform_point(Vec3::zero());
+        assert_close(below.x(), 0.0);
+        assert_close(below.y(), 0.0);
+        assert!(below.z() > -1.0 && below.z() < 1.0);
+        let edge = m.transform_point(Vec3(4.0 * 30f32.to_radians().tan(), 0.0, 0.0));
+        assert_close(edge.x().abs().max(edge.y().abs()), 1.0);
+    }
+
+    #[test]
+    fn maps_follow_shadow_casting_lights() {
+        let _headless = test_context(16, 16);
+        let lights = [
+            Light::point(Vec3(0.0, 3.0, 0.0), WHITE, 1.0).with_shadows(),
+            Light::directional(Vec3(-1.0, -1.0, 0.0), WHITE, 1.0).with_shadows(),
+            Light::directional(Vec3(1.0, -1.0, 0.0), WHITE, 1.0),
+            Light::spot(Vec3(0.0, 4.0, 0.0), Vec3(0.0, -1.0, 0.0), Degrees(20.0), Degrees(30.0), WHITE, 1.0).with_shadows(),
+        ];
+        let mut pass = ShadowPass::new(32).unwrap();
+        let mut drawn = Vec::new();
+        pass.render(&lights, Vec3::zero(), 10.0, |light_space, _| drawn.push(light_space.clone())).unwrap();
+
+        assert_eq!(pass.maps().iter().map(ShadowMap::light).collect::<Vec<_>>(), [1, 3]);
+        for (map, drawn) in pass.maps().iter().zip(&drawn) {
+            assert_eq!(map.light_space().0, drawn.0);
+            assert_eq!(light_space(&lights[map.light()], Vec3::zero(), 10.0).unwrap().0, drawn.0);
+        }
+        assert_eq!(drawn.len(), 2);
+    }
+}
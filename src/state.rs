@@ -0,0 +1,226 @@
+use crate::gl;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl DepthFunc {
+    fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            DepthFunc::Never => gl::NEVER,
+            DepthFunc::Less => gl::LESS,
+            DepthFunc::Equal => gl::EQUAL,
+            DepthFunc::LessEqual => gl::LEQUAL,
+            DepthFunc::Greater => gl::GREATER,
+            DepthFunc::NotEqual => gl::NOTEQUAL,
+            DepthFunc::GreaterEqual => gl::GEQUAL,
+            DepthFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+/// Which faces are culled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cull {
+    None,
+    Back,
+    Front,
+    Both,
+}
+
+/// Winding of front faces, as seen on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winding {
+    CounterClockwise,
+    Clockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    None,
+    /// Classic transparency with straight alpha.
+    Alpha,
+    /// Transparency with colors already multiplied by alpha.
+    Premultiplied,
+    Additive,
+}
+
+/// Fixed-function state applied before each draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_func: DepthFunc,
+    pub cull: Cull,
+    pub winding: Winding,
+    pub blend: Blend,
+}
+
+impl Default for RenderState {
+    /// Opaque geometry: depth tested and written, back faces culled.
+    fn default() -> Self {
+        Self {
+            depth_test: true,
+            depth_write: true,
+            depth_func: DepthFunc::Less,
+            cull: Cull::Back,
+            winding: Winding::CounterClockwise,
+            blend: Blend::None,
+        }
+    }
+}
+
+/// States an object sets differently from the renderer's `RenderState`. `None` keeps the
+/// renderer's value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderOverrides {
+    pub depth_test: Option<bool>,
+    pub depth_write: Option<bool>,
+    pub depth_func: Option<DepthFunc>,
+    pub cull: Option<Cull>,
+    pub winding: Option<Winding>,
+    pub blend: Option<Blend>,
+}
+
+impl RenderState {
+    /// This state with `overrides` applied on top.
+    pub fn with(&self, overrides: &RenderOverrides) -> RenderState {
+        RenderState {
+            depth_test: overrides.depth_test.unwrap_or(self.depth_test),
+            depth_write: overrides.depth_write.unwrap_or(self.depth_write),
+            depth_func: overrides.depth_func.unwrap_or(self.depth_func),
+            cull: overrides.cull.unwrap_or(self.cull),
+            winding: overrides.winding.unwrap_or(self.winding),
+            blend: overrides.blend.unwrap_or(self.blend),
+        }
+    }
+
+    pub fn apply(&self) {
+        unsafe {
+            set_enabled(gl::DEPTH_TEST, self.depth_test);
+            gl::DepthMask(self.depth_write as _);
+            gl::DepthFunc(self.depth_func.gl_enum());
+
+            set_enabled(gl::CULL_FACE, self.cull != Cull::None);
+            match self.cull {
+                Cull::None => {}
+                Cull::Back => gl::CullFace(gl::BACK),
+                Cull::Front => gl::CullFace(gl::FRONT),
+                Cull::Both => gl::CullFace(gl::FRONT_AND_BACK),
+            }
+            gl::FrontFace(match self.winding {
+                Winding::CounterClockwise => gl::CCW,
+                Winding::Clockwise => gl::CW,
+            });
+
+            set_enabled(gl::BLEND, self.blend != Blend::None);
+            match self.blend {
+                Blend::None => {}
+                Blend::Alpha => gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+                Blend::Premultiplied => gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+                Blend::Additive => gl::BlendFunc(gl::ONE, gl::ONE),
+            }
+        }
+    }
+}
+
+/// Values the color, depth and stencil buffers are cleared to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearState {
+    pub color: [f32; 4],
+    pub depth: f32,
+    pub stencil: i32,
+}
+
+impl Default for ClearState {
+    fn default() -> Self {
+        Self {
+            color: [0.0, 0.0, 0.0, 1.0],
+            depth: 1.0,
+            stencil: 0,
+        }
+    }
+}
+
+impl ClearState {
+    /// Clears the bound framebuffer's color, depth and stencil buffers.
+    pub fn clear(&self) {
+        unsafe {
+            let [red, green, blue, alpha] = self.color;
+            gl::ClearColor(red, green, blue, alpha);
+            gl::ClearDepth(self.depth as f64);
+            gl::ClearStencil(self.stencil);
+            // Writes disabled by the last draw would also block the clear.
+            gl::DepthMask(gl::TRUE);
+            gl::StencilMask(!0);
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+    }
+}
+
+/// Region of the framebuffer drawn to, in pixels from the bottom-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Viewport {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self { x: 0, y: 0, width, height }
+    }
+
+    pub fn current() -> Self {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let [x, y, width, height] = viewport;
+        Self { x, y, width, height }
+    }
+
+    pub fn apply(&self) {
+        unsafe {
+            gl::Viewport(self.x, self.y, self.width, self.height);
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+}
+
+unsafe fn set_enabled(capability: gl::types::GLenum, enabled: bool) {
+    if enabled {
+        gl::Enable(capability);
+    } else {
+        gl::Disable(capability);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_only_what_they_set() {
+        let base = RenderState::default();
+        assert_eq!(base.with(&RenderOverrides::default()), base);
+        let overrides = RenderOverrides { blend: Some(Blend::Alpha), depth_write: Some(false), ..RenderOverrides::default() };
+        let state = base.with(&overrides);
+        assert_eq!(state.blend, Blend::Alpha);
+        assert!(!state.depth_write);
+        assert_eq!((state.depth_test, state.cull, state.depth_func), (true, Cull::Back, DepthFunc::Less));
+    }
+}
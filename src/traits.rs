@@ -1,14 +1,35 @@
-use std::ffi::CString;
+use std::rc::Rc;
+use crate::frame;
 use crate::gl;
-use crate::math::{Camera, Mat4x4, Vec3};
+use crate::light::{Light, Lighting};
+use crate::math::{Camera, Mat4x4, Quat, Trs, Vec3};
+use crate::mesh::Mesh;
+use crate::shader::{Program, ShaderCache, ShaderError, ShaderKey};
+use crate::shadow;
+use crate::state::{RenderOverrides, RenderState};
+use crate::uniform::{Uniform, Uniforms};
 
 pub trait Colored {
     fn set_color(&mut self, red: u8, green: u8, blue: u8);
     fn get_color(&self) -> (u8, u8, u8);
+    fn set_alpha(&mut self, alpha: u8);
+    fn get_alpha(&self) -> u8;
 }
 
 pub trait Shadowed {
-    fn light_source<P: Positioned>(&mut self, position: P);
+    fn light_source(&mut self, light: Light);
+    fn get_light_sources(&self) -> &[Light];
+    fn clear_light_sources(&mut self);
+    fn set_casts_shadows(&mut self, casts: bool);
+    fn get_casts_shadows(&self) -> bool;
+    fn set_receives_shadows(&mut self, receives: bool);
+    fn get_receives_shadows(&self) -> bool;
+}
+
+/// Per-object exceptions to the renderer's `RenderState`, e.g. blending for transparent objects.
+pub trait Rendered {
+    fn get_render_overrides(&self) -> &RenderOverrides;
+    fn set_render_overrides(&mut self, overrides: RenderOverrides);
 }
 
 pub trait Positioned {
@@ -17,6 +38,7 @@ pub trait Positioned {
 
 pub trait Rotated {
     fn rotate_around(&mut self, d: f32, v: Vec3);
+    fn rotate_by(&mut self, q: Quat);
 }
 
 pub trait Scaled {
@@ -30,37 +52,67 @@ pub trait Visible {
 }
 
 pub trait Transform {
-    fn get_matrix(&self) -> Mat4x4;
-    fn set_matrix(&mut self, matrix: Mat4x4);
+    fn get_trs(&self) -> &Trs;
+    fn get_trs_mut(&mut self) -> &mut Trs;
+
+    fn get_matrix(&self) -> Mat4x4 {
+        self.get_trs().matrix()
+    }
+
+    /// Decomposes `matrix`, so it must be made of translation, rotation and scale only.
+    fn set_matrix(&mut self, matrix: Mat4x4) {
+        *self.get_trs_mut() = Trs::from_matrix(&matrix);
+    }
+
+    fn position(&self) -> Vec3 {
+        self.get_trs().translation()
+    }
+
+    fn set_position(&mut self, position: Vec3) {
+        self.get_trs_mut().set_translation(position);
+    }
+
+    fn rotation(&self) -> Quat {
+        self.get_trs().rotation()
+    }
+
+    fn set_rotation(&mut self, rotation: Quat) {
+        self.get_trs_mut().set_rotation(rotation);
+    }
+
+    fn scale(&self) -> Vec3 {
+        self.get_trs().scale()
+    }
+
+    fn set_scale(&mut self, scale: Vec3) {
+        self.get_trs_mut().set_scale(scale);
+    }
 }
 
 impl<T: Transform> Positioned for T {
     fn translate_by(&mut self, v: Vec3) {
-        let mut m = self.get_matrix();
-        m.translate(v);
-        self.set_matrix(m);
+        self.set_position(self.position() + v);
     }
 }
 
+/// Rotations are applied around the object's own position.
 impl<T: Transform> Rotated for T {
     fn rotate_around(&mut self, d: f32, v: Vec3) {
-        let mut m = self.get_matrix();
-        m.rotate(d, v);
-        self.set_matrix(m);
+        self.rotate_by(Quat::from_axis_angle(d, v));
+    }
+
+    fn rotate_by(&mut self, q: Quat) {
+        self.set_rotation(q * self.rotation());
     }
 }
 
 impl<T: Transform> Scaled for T {
     fn scale_by(&mut self, v: Vec3) {
-        let mut m = self.get_matrix();
-        m.scale(v);
-        self.set_matrix(m);
+        self.set_scale(self.scale() * v);
     }
 
     fn scaled_by(&mut self, d: f32) {
-        let mut m = self.get_matrix();
-        m.scale(Vec3(d, d, d));
-        self.set_matrix(m);
+        self.set_scale(self.scale() * Vec3(d, d, d));
     }
 }
 
@@ -71,234 +123,105 @@ pub trait Meshed {
     fn set_indices(&mut self, indices: &[u32]);
     fn get_normals(&self) -> &[f32];
     fn set_normals(&mut self, normals: &[f32]);
+    fn get_mesh(&self) -> &Mesh;
 }
 
 pub trait Drawable {
+    /// Draws the object on its own, unlit, as a one-off. It uploads the frame uniforms for
+    /// `camera` first, so don't call it in a loop: draw a `Scene`, or call `frame::begin` once
+    /// per frame and then `draw_with` for each object.
     fn draw(&self, camera: &Camera);
+    /// Draws with `model` in place of the object's own matrix, e.g. a world matrix from a scene,
+    /// lit by `lighting` on top of the object's own light sources, with `state` as the base
+    /// render state. The camera comes from the frame uniforms, see `frame::begin`.
+    fn draw_with(&self, model: &Mat4x4, lighting: &Lighting, state: &RenderState);
+    /// Draws depth only into a shadow map with the depth `program`, if the object casts shadows.
+    fn draw_shadow(&self, model: &Mat4x4, light_space: &Mat4x4, program: &Program);
 }
 
 pub trait Shaded {
-    fn get_shader_program(&self) -> u32;
-    fn set_shader_program(&mut self, id: u32);
-    fn compile_shaders(&mut self, vertex: Option<&str>, fragment: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-        unsafe {
-            let vertex_shader_id = gl::CreateShader(gl::VERTEX_SHADER);
-            let fragment_shader_id = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let common_vertex_shader_id = gl::CreateShader(gl::VERTEX_SHADER);
-            let common_fragment_shader_id = gl::CreateShader(gl::FRAGMENT_SHADER);
-
-            let vertex_shader_code = match vertex {
-                Some(path) => std::fs::read_to_string(path)?,
-                None => String::from("#version 460 core
-layout(location = 0) in vec3 vPos;
-
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
-
-void main() {
-    gl_Position = projection * view * model * vec4(vPos, 1.0);
-}")
-            };
-
-            let fragment_shader_code = match fragment {
-                Some(path) => std::fs::read_to_string(path)?,
-                None => String::from("#version 460 core
-out vec3 color;
-
-void main() {
-    color = vec3(0.2, 0.7, 0.9);
-}")
-            };
-
-            let cstring = CString::new(vertex_shader_code)?;
-            let pointer = cstring.as_ptr();
-            gl::ShaderSource(vertex_shader_id, 1, &pointer, core::ptr::null());
-            gl::CompileShader(vertex_shader_id);
-
-            let mut result = 0;
-            let mut info_log_length = 0;
-            gl::GetShaderiv(vertex_shader_id, gl::COMPILE_STATUS, &mut result);
-            gl::GetShaderiv(vertex_shader_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
-            if info_log_length > 0 {
-                let mut vec = Vec::with_capacity(info_log_length as usize + 1);
-                vec.extend([b' '].iter().cycle().take(info_log_length as usize));
-                let cs = CString::from_vec_with_nul_unchecked(vec);
-                gl::GetShaderInfoLog(vertex_shader_id, info_log_length, core::ptr::null_mut(), cs.as_ptr() as *mut gl::types::GLchar);
-                println!("{}", cs.to_str()?);
-            }
-
-            let cstring = CString::new(std::fs::read_to_string("shaders/common.vert").unwrap())?;
-            let pointer = cstring.as_ptr();
-            gl::ShaderSource(common_vertex_shader_id, 1, &pointer, core::ptr::null());
-            gl::CompileShader(common_vertex_shader_id);
-
-            let mut result = 0;
-            let mut info_log_length = 0;
-            gl::GetShaderiv(common_vertex_shader_id, gl::COMPILE_STATUS, &mut result);
-            gl::GetShaderiv(common_vertex_shader_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
-            if info_log_length > 0 {
-                let mut vec = Vec::with_capacity(info_log_length as usize + 1);
-                vec.extend([b' '].iter().cycle().take(info_log_length as usize));
-                let cs = CString::from_vec_with_nul_unchecked(vec);
-                gl::GetShaderInfoLog(common_vertex_shader_id, info_log_length, core::ptr::null_mut(), cs.as_ptr() as *mut gl::types::GLchar);
-                println!("{}", cs.to_str()?);
-            }
-
-            let cstring = CString::new(fragment_shader_code)?;
-            let pointer = cstring.as_ptr();
-            gl::ShaderSource(fragment_shader_id, 1, &pointer, core::ptr::null());
-            gl::CompileShader(fragment_shader_id);
-
-            let mut result = 0;
-            let mut info_log_length = 0;
-            gl::GetShaderiv(fragment_shader_id, gl::COMPILE_STATUS, &mut result);
-            gl::GetShaderiv(fragment_shader_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
-            if info_log_length > 0 {
-                let mut vec = Vec::with_capacity(info_log_length as usize + 1);
-                vec.extend([b' '].iter().cycle().take(info_log_length as usize));
-                let cs = CString::from_vec_with_nul_unchecked(vec);
-                gl::GetShaderInfoLog(fragment_shader_id, info_log_length, core::ptr::null_mut(), cs.as_ptr() as *mut gl::types::GLchar);
-                println!("{}", cs.to_str()?);
-            }
-
-            let cstring = CString::new(std::fs::read_to_string("shaders/common.frag").unwrap())?;
-            let pointer = cstring.as_ptr();
-            gl::ShaderSource(common_fragment_shader_id, 1, &pointer, core::ptr::null());
-            gl::CompileShader(common_fragment_shader_id);
-
-            let mut result = 0;
-            let mut info_log_length = 0;
-            gl::GetShaderiv(common_fragment_shader_id, gl::COMPILE_STATUS, &mut result);
-            gl::GetShaderiv(common_fragment_shader_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
-            if info_log_length > 0 {
-                let mut vec = Vec::with_capacity(info_log_length as usize + 1);
-                vec.extend([b' '].iter().cycle().take(info_log_length as usize));
-                let cs = CString::from_vec_with_nul_unchecked(vec);
-                gl::GetShaderInfoLog(common_fragment_shader_id, info_log_length, core::ptr::null_mut(), cs.as_ptr() as *mut gl::types::GLchar);
-                println!("{}", cs.to_str()?);
-            }
-
-            let program_id = gl::CreateProgram();
-            gl::AttachShader(program_id, common_vertex_shader_id);
-            gl::AttachShader(program_id, vertex_shader_id);
-            gl::AttachShader(program_id, common_fragment_shader_id);
-            gl::AttachShader(program_id, fragment_shader_id);
-            gl::LinkProgram(program_id);
-
-            gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut result);
-            gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut info_log_length);
-            if info_log_length > 0 {
-                let mut vec = Vec::with_capacity(info_log_length as usize + 1);
-                vec.extend([b' '].iter().cycle().take(info_log_length as usize));
-                let cs = CString::from_vec_with_nul_unchecked(vec);
-                gl::GetProgramInfoLog(program_id, info_log_length, core::ptr::null_mut(), cs.as_ptr() as *mut gl::types::GLchar);
-                println!("{}", cs.to_str()?);
-            }
-
-            gl::DetachShader(program_id, common_vertex_shader_id);
-            gl::DetachShader(program_id, vertex_shader_id);
-            gl::DetachShader(program_id, common_fragment_shader_id);
-            gl::DetachShader(program_id, fragment_shader_id);
-
-            gl::DeleteShader(common_vertex_shader_id);
-            gl::DeleteShader(vertex_shader_id);
-            gl::DeleteShader(common_fragment_shader_id);
-            gl::DeleteShader(fragment_shader_id);
-
-            self.set_shader_program(program_id);
-
-            Ok(())
-        }
+    fn get_program(&self) -> Option<&Rc<Program>>;
+    fn set_shader_program(&mut self, program: Rc<Program>);
+    fn get_shader_program(&self) -> u32 {
+        self.get_program().map_or(0, |p| p.id())
+    }
+    fn get_uniforms(&self) -> &Uniforms;
+    fn get_uniforms_mut(&mut self) -> &mut Uniforms;
+    /// Sets a uniform for this object only. The value is written to the shader program whenever
+    /// the object is drawn, so other objects sharing the program keep their own values. Use
+    /// `Program::set_uniform` for a value shared by the whole program.
+    fn set_uniform(&mut self, name: &str, value: impl Uniform + 'static) where Self: Sized {
+        self.get_uniforms_mut().set(name, value);
+    }
+    fn compile_shaders(&mut self, vertex: Option<&str>, fragment: Option<&str>) -> Result<(), ShaderError> {
+        self.compile_shader_variant(vertex, fragment, &[])
+    }
+    /// Like `compile_shaders`, with `#define`s injected into every stage. Programs are shared
+    /// through the `ShaderCache` with everything compiled from the same paths and defines.
+    fn compile_shader_variant(&mut self, vertex: Option<&str>, fragment: Option<&str>, defines: &[(&str, &str)]) -> Result<(), ShaderError> {
+        let key = ShaderKey::new(vertex, fragment, defines);
+        let program = ShaderCache::with(|cache| cache.get(&key))?;
+        self.set_shader_program(program);
+        Ok(())
     }
 }
 
-impl<T: Transform + Visible + Meshed + Shaded> Drawable for T {
+impl<T: Transform + Visible + Meshed + Shaded + Colored + Shadowed + Rendered> Drawable for T {
     fn draw(&self, camera: &Camera) {
+        frame::begin(camera);
+        self.draw_with(&self.get_matrix(), &Lighting::default(), &RenderState::default());
+    }
+
+    fn draw_with(&self, model: &Mat4x4, lighting: &Lighting, state: &RenderState) {
         unsafe {
             if !self.get_visibility() { return; }
-            let mut vao = 0;
-            gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
-
-            let mut array_buffer = 0;
-            gl::GenBuffers(1, &mut array_buffer);
-            gl::BindBuffer(gl::ARRAY_BUFFER, array_buffer);
-            let vertices = self.get_vertices();
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                std::mem::size_of_val(vertices) as _,
-                vertices.as_ptr() as _,
-                gl::STATIC_DRAW,
-            );
+            let Some(program) = self.get_program() else { return; };
+            let index_count = self.get_mesh().bind(self);
 
-            let mut element_array_buffer = 0;
-            gl::GenBuffers(1, &mut element_array_buffer);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, element_array_buffer);
-            let indices = self.get_indices();
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                std::mem::size_of_val(indices) as _,
-                indices.as_ptr() as _,
-                gl::STATIC_DRAW,
-            );
+            gl::UseProgram(program.id());
+            state.with(self.get_render_overrides()).apply();
 
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (3 * std::mem::size_of::<f32>()) as _,
-                0 as _,
-            );
-            gl::EnableVertexAttribArray(0);
-
-            let mut normal_buffer = 0;
-            gl::GenBuffers(1, &mut normal_buffer);
-            gl::BindBuffer(gl::ARRAY_BUFFER, normal_buffer);
-            let vertices = self.get_normals();
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                std::mem::size_of_val(vertices) as _,
-                vertices.as_ptr() as _,
-                gl::STATIC_DRAW,
-            );
+            let normal_matrix = model.inverse().map_or_else(|| model.clone(), |m| m.transpose());
+            let (red, green, blue) = self.get_color();
+            let color = (red as f32 / 255.0, green as f32 / 255.0, blue as f32 / 255.0, self.get_alpha() as f32 / 255.0);
+            program.set_builtin_uniform("model", model);
+            program.set_builtin_uniform("normalMatrix", &normal_matrix);
+            program.set_builtin_uniform("color", color);
 
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (3 * std::mem::size_of::<f32>()) as _,
-                0 as _,
-            );
-            gl::EnableVertexAttribArray(1);
+            let lights = lighting.lights.iter().chain(self.get_light_sources());
+            Light::upload(program, lights, lighting.shadow_maps);
+            shadow::upload(program, lighting.shadow_maps, self.get_receives_shadows());
+            self.get_uniforms().upload(program);
 
-            gl::UseProgram(self.get_shader_program());
+            gl::DrawElements(
+                gl::TRIANGLES,
+                index_count as _,
+                gl::UNSIGNED_INT,
+                core::ptr::null(),
+            );
 
-            let cstring = CString::new("model").unwrap();
-            let loc = gl::GetUniformLocation(self.get_shader_program(), cstring.as_ptr());
-            gl::UniformMatrix4fv(loc, 1, gl::TRUE, self.get_matrix().0.as_ptr());
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+        }
+    }
 
-            let cstring = CString::new("view").unwrap();
-            let loc = gl::GetUniformLocation(self.get_shader_program(), cstring.as_ptr());
-            gl::UniformMatrix4fv(loc, 1, gl::TRUE, camera.view.0.as_ptr());
+    fn draw_shadow(&self, model: &Mat4x4, light_space: &Mat4x4, program: &Program) {
+        unsafe {
+            if !self.get_visibility() || !self.get_casts_shadows() { return; }
+            let index_count = self.get_mesh().bind(self);
 
-            let cstring = CString::new("projection").unwrap();
-            let loc = gl::GetUniformLocation(self.get_shader_program(), cstring.as_ptr());
-            gl::UniformMatrix4fv(loc, 1, gl::TRUE, camera.projection.0.as_ptr());
+            gl::UseProgram(program.id());
+            program.set_builtin_uniform("model", model);
+            program.set_builtin_uniform("shadowLightSpace", light_space);
 
             gl::DrawElements(
                 gl::TRIANGLES,
-                indices.len() as _,
+                index_count as _,
                 gl::UNSIGNED_INT,
-                0 as _,
+                core::ptr::null(),
             );
 
-            gl::DeleteBuffers(1, &array_buffer);
-            gl::DeleteBuffers(1, &element_array_buffer);
-            gl::DeleteBuffers(1, &normal_buffer);
-
+            gl::BindVertexArray(0);
             gl::UseProgram(0);
         }
     }
@@ -307,15 +230,25 @@ impl<T: Transform + Visible + Meshed + Shaded> Drawable for T {
 #[macro_export]
 macro_rules! object {
     ($name:ident($v:expr, $f:expr) { $($id:ident: $ty:ty),* }) => {
-        use crate::math::*;
-        use crate::traits::*;
+        // Glob imports, so that several objects can be declared in the same module.
+        #[allow(unused_imports)]
+        use $crate::math::*;
+        #[allow(unused_imports)]
+        use $crate::traits::*;
         pub struct $name {
             vertices: Vec<f32>,
             indices: Vec<u32>,
-            matrix: Mat4x4,
+            transform: Trs,
             visible: bool,
-            program: u32,
+            program: Option<std::rc::Rc<$crate::shader::Program>>,
+            uniforms: $crate::uniform::Uniforms,
             normals: Vec<f32>,
+            mesh: $crate::mesh::Mesh,
+            color: [u8; 4],
+            lights: Vec<$crate::light::Light>,
+            casts_shadows: bool,
+            receives_shadows: bool,
+            render_overrides: $crate::state::RenderOverrides,
             $(
             $id: $ty,
             )*
@@ -330,12 +263,60 @@ macro_rules! object {
             }
         }
 
+        impl Colored for $name {
+            fn set_color(&mut self, red: u8, green: u8, blue: u8) {
+                self.color = [red, green, blue, self.color[3]];
+            }
+            fn get_color(&self) -> (u8, u8, u8) {
+                (self.color[0], self.color[1], self.color[2])
+            }
+            fn set_alpha(&mut self, alpha: u8) {
+                self.color[3] = alpha;
+            }
+            fn get_alpha(&self) -> u8 {
+                self.color[3]
+            }
+        }
+
+        impl Shadowed for $name {
+            fn light_source(&mut self, light: $crate::light::Light) {
+                self.lights.push(light);
+            }
+            fn get_light_sources(&self) -> &[$crate::light::Light] {
+                &self.lights[..]
+            }
+            fn clear_light_sources(&mut self) {
+                self.lights.clear();
+            }
+            fn set_casts_shadows(&mut self, casts: bool) {
+                self.casts_shadows = casts;
+            }
+            fn get_casts_shadows(&self) -> bool {
+                self.casts_shadows
+            }
+            fn set_receives_shadows(&mut self, receives: bool) {
+                self.receives_shadows = receives;
+            }
+            fn get_receives_shadows(&self) -> bool {
+                self.receives_shadows
+            }
+        }
+
+        impl Rendered for $name {
+            fn get_render_overrides(&self) -> &$crate::state::RenderOverrides {
+                &self.render_overrides
+            }
+            fn set_render_overrides(&mut self, overrides: $crate::state::RenderOverrides) {
+                self.render_overrides = overrides;
+            }
+        }
+
         impl Transform for $name {
-            fn get_matrix(&self) -> Mat4x4 {
-                self.matrix.clone()
+            fn get_trs(&self) -> &Trs {
+                &self.transform
             }
-            fn set_matrix(&mut self, matrix: Mat4x4) {
-                self.matrix = matrix;
+            fn get_trs_mut(&mut self) -> &mut Trs {
+                &mut self.transform
             }
         }
 
@@ -345,51 +326,78 @@ macro_rules! object {
             }
             fn set_vertices(&mut self, vertices: &[f32]) {
                 self.vertices = vertices.to_vec();
+                self.mesh.mark_dirty();
             }
             fn get_indices(&self) -> &[u32] {
                 &self.indices[..]
             }
             fn set_indices(&mut self, indices: &[u32]) {
                 self.indices = indices.to_vec();
+                self.mesh.mark_dirty();
             }
             fn get_normals(&self) -> &[f32] {
                 &self.normals[..]
             }
             fn set_normals(&mut self, normals: &[f32]) {
                 self.normals = normals.to_vec();
+                self.mesh.mark_dirty();
+            }
+            fn get_mesh(&self) -> &$crate::mesh::Mesh {
+                &self.mesh
             }
         }
 
         impl Shaded for $name {
-            fn get_shader_program(&self) -> u32 {
-                self.program
+            fn get_program(&self) -> Option<&std::rc::Rc<$crate::shader::Program>> {
+                self.program.as_ref()
+            }
+            fn set_shader_program(&mut self, program: std::rc::Rc<$crate::shader::Program>) {
+                self.program = Some(program);
+            }
+            fn get_uniforms(&self) -> &$crate::uniform::Uniforms {
+                &self.uniforms
             }
-            fn set_shader_program(&mut self, id: u32) {
-                self.program = id;
+            fn get_uniforms_mut(&mut self) -> &mut $crate::uniform::Uniforms {
+                &mut self.uniforms
             }
         }
 
         impl $name {
-            pub fn empty($($id: $ty)*) -> Self {
-                let matrix = Mat4x4::identity();
+            pub fn empty($($id: $ty)*) -> Result<Self, $crate::shader::ShaderError> {
+                let transform = Trs::identity();
                 let visible = true;
                 let vertices = Vec::new();
                 let indices = Vec::new();
                 let normals = Vec::new();
-                let program = 0;
+                let program = None;
+                let uniforms = $crate::uniform::Uniforms::default();
+                let mesh = $crate::mesh::Mesh::new();
+                let color = [255; 4];
+                let lights = Vec::new();
+                let casts_shadows = true;
+                let receives_shadows = true;
+                let render_overrides = $crate::state::RenderOverrides::default();
                 let mut sself = Self {
-                    matrix,
+                    transform,
                     visible,
                     vertices,
                     indices,
                     program,
+                    uniforms,
                     normals,
+                    mesh,
+                    color,
+                    lights,
+                    casts_shadows,
+                    receives_shadows,
+                    render_overrides,
                     $($id,)*
                 };
-                sself.compile_shaders(Some($v), Some($f)).unwrap();
-                sself
+                sself.compile_shaders(Some($v), Some($f))?;
+                Ok(sself)
             }
 
+            #[allow(clippy::identity_op)]
             pub fn calculate_normals(&mut self) {
                 self.normals = vec![0.0; self.vertices.len()];
                 let mut chunks = self.indices.chunks(3);
@@ -416,6 +424,7 @@ macro_rules! object {
                         (*f, *s, *t) = (v.0, v.1, v.2);
                     } else { unreachable!() }
                 });
+                self.mesh.mark_dirty();
             }
         }
     };
@@ -0,0 +1,233 @@
+use crate::gl;
+use crate::math::{Mat4x4, Vec3};
+use crate::shader::Program;
+
+/// A value that can be written to a uniform of a linked program.
+pub trait Uniform {
+    /// Writes the value at `location` of `program`, which doesn't need to be in use.
+    ///
+    /// # Safety
+    ///
+    /// A GL context must be current and `program` must be a program linked in it.
+    unsafe fn upload(&self, program: u32, location: i32);
+}
+
+/// Uniform values of one object, written to its program every time the object is drawn, so
+/// objects sharing a program each keep their own.
+#[derive(Default)]
+pub struct Uniforms {
+    values: Vec<(String, Box<dyn Uniform>)>,
+}
+
+impl Uniforms {
+    /// Sets `name` to `value`, replacing any earlier value.
+    pub fn set(&mut self, name: &str, value: impl Uniform + 'static) {
+        match self.values.iter_mut().find(|(n, _)| n == name) {
+            Some((_, old)) => *old = Box::new(value),
+            None => self.values.push((name.to_string(), Box::new(value))),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Writes every value to `program`.
+    pub fn upload(&self, program: &Program) {
+        for (name, value) in &self.values {
+            program.set_uniform(name, &**value);
+        }
+    }
+}
+
+/// A 2D texture bound to a texture unit, for `sampler2D` uniforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Texture {
+    pub id: u32,
+    pub unit: u32,
+}
+
+impl Uniform for f32 {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform1f(program, location, *self);
+    }
+}
+
+impl Uniform for i32 {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform1i(program, location, *self);
+    }
+}
+
+impl Uniform for bool {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform1i(program, location, *self as i32);
+    }
+}
+
+impl Uniform for Vec3 {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform3f(program, location, self.0, self.1, self.2);
+    }
+}
+
+/// A `vec2`.
+impl Uniform for (f32, f32) {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform2f(program, location, self.0, self.1);
+    }
+}
+
+/// A `vec4`.
+impl Uniform for (f32, f32, f32, f32) {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform4f(program, location, self.0, self.1, self.2, self.3);
+    }
+}
+
+impl Uniform for Mat4x4 {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniformMatrix4fv(program, location, 1, gl::TRUE, self.0.as_ptr());
+    }
+}
+
+impl Uniform for Texture {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ActiveTexture(gl::TEXTURE0 + self.unit);
+        gl::BindTexture(gl::TEXTURE_2D, self.id);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::ProgramUniform1i(program, location, self.unit as i32);
+    }
+}
+
+impl Uniform for [f32] {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform1fv(program, location, self.len() as _, self.as_ptr());
+    }
+}
+
+impl Uniform for [i32] {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        gl::ProgramUniform1iv(program, location, self.len() as _, self.as_ptr());
+    }
+}
+
+impl Uniform for [Vec3] {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        let values = self.iter().flat_map(|v| [v.0, v.1, v.2]).collect::<Vec<_>>();
+        gl::ProgramUniform3fv(program, location, self.len() as _, values.as_ptr());
+    }
+}
+
+impl Uniform for [Mat4x4] {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        let values = self.iter().flat_map(|m| m.0).collect::<Vec<_>>();
+        gl::ProgramUniformMatrix4fv(program, location, self.len() as _, gl::TRUE, values.as_ptr());
+    }
+}
+
+/// Consecutive texture units starting at the first texture's.
+impl Uniform for [Texture] {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        for texture in self {
+            gl::ActiveTexture(gl::TEXTURE0 + texture.unit);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+        }
+        gl::ActiveTexture(gl::TEXTURE0);
+        let units = self.iter().map(|t| t.unit as i32).collect::<Vec<_>>();
+        gl::ProgramUniform1iv(program, location, units.len() as _, units.as_ptr());
+    }
+}
+
+impl<T, const N: usize> Uniform for [T; N] where [T]: Uniform {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        self[..].upload(program, location);
+    }
+}
+
+impl<T> Uniform for Vec<T> where [T]: Uniform {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        self[..].upload(program, location);
+    }
+}
+
+impl<T: Uniform + ?Sized> Uniform for &T {
+    unsafe fn upload(&self, program: u32, location: i32) {
+        (**self).upload(program, location);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::headless::test_context;
+    use crate::shader::{ShaderCache, ShaderKey};
+
+    const VERTEX: &str = "#version 460 core
+layout(location = 0) in vec3 vPos;
+uniform float weights[3];
+uniform vec3 tint;
+out vec3 shade;
+void main() {
+    shade = tint * (weights[0] + weights[1] + weights[2]);
+    gl_Position = vec4(vPos, 1.0);
+}";
+    const FRAGMENT: &str = "#version 460 core
+in vec3 shade;
+out vec4 fragColor;
+void main() {
+    fragColor = vec4(shade, 1.0);
+}";
+
+    fn read(program: &Program, name: &str, count: usize) -> Vec<f32> {
+        let mut values = vec![0.0; count];
+        unsafe { gl::GetUniformfv(program.id(), program.location(name), values.as_mut_ptr()) };
+        values
+    }
+
+    #[test]
+    fn values_replace_by_name() {
+        let mut uniforms = Uniforms::default();
+        uniforms.set("tint", Vec3(1.0, 0.0, 0.0));
+        uniforms.set("weights", [1.0, 2.0, 3.0]);
+        uniforms.set("tint", Vec3(0.0, 1.0, 0.0));
+        assert_eq!(uniforms.len(), 2);
+    }
+
+    #[test]
+    fn uploads_follow_values_and_lengths() {
+        let _headless = test_context(16, 16);
+        let directory = std::env::temp_dir().join(format!("uniform-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let (vertex, fragment) = (directory.join("test.vert"), directory.join("test.frag"));
+        fs::write(&vertex, VERTEX).unwrap();
+        fs::write(&fragment, FRAGMENT).unwrap();
+        let key = ShaderKey::new(vertex.to_str(), fragment.to_str(), &[]);
+        let program = ShaderCache::default().get(&key).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        // Two objects sharing the program each write their own values.
+        let mut first = Uniforms::default();
+        first.set("weights", [1.0, 2.0, 3.0]);
+        first.set("tint", Vec3(1.0, 0.5, 0.25));
+        let mut second = Uniforms::default();
+        second.set("tint", Vec3(0.0, 0.0, 1.0));
+        first.upload(&program);
+        assert_eq!(read(&program, "tint", 3), [1.0, 0.5, 0.25]);
+        second.upload(&program);
+        assert_eq!(read(&program, "tint", 3), [0.0, 0.0, 1.0]);
+        assert_eq!(read(&program, "weights[2]", 1), [3.0]);
+
+        // Slices only write as many elements as they hold.
+        program.set_uniform("weights", vec![4.0, 5.0]);
+        assert_eq!(read(&program, "weights[0]", 1), [4.0]);
+        assert_eq!(read(&program, "weights[1]", 1), [5.0]);
+        assert_eq!(read(&program, "weights[2]", 1), [3.0]);
+        assert_eq!(program.location("missing"), -1);
+    }
+}
@@ -0,0 +1,103 @@
+use std::ffi::{CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Watches a directory with inotify and reports the files written to it since the last call.
+pub struct FileWatcher {
+    fd: i32,
+    directory: PathBuf,
+}
+
+impl FileWatcher {
+    pub fn new(directory: impl AsRef<Path>) -> Result<Self, String> {
+        let directory = directory.as_ref().to_path_buf();
+        let cstring = CString::new(directory.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+        unsafe {
+            let fd = libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC);
+            if fd < 0 {
+                return Err(format!("Couldn't initialize inotify: {}", std::io::Error::last_os_error()));
+            }
+            // Editors often save by writing a new file and renaming it over the old one. Created
+            // files aren't reported until they are closed, since they start out empty.
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+            if libc::inotify_add_watch(fd, cstring.as_ptr(), mask) < 0 {
+                let error = std::io::Error::last_os_error();
+                libc::close(fd);
+                return Err(format!("Couldn't watch {}: {}", directory.display(), error));
+            }
+            Ok(Self { fd, directory })
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Files changed since the last call, without duplicates. Never blocks.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let length = unsafe { libc::read(self.fd, buffer.as_mut_ptr() as *mut _, buffer.len()) };
+            if length <= 0 {
+                break;
+            }
+            let mut offset = 0;
+            while offset < length as usize {
+                let event = unsafe { (buffer.as_ptr().add(offset) as *const libc::inotify_event).read_unaligned() };
+                let start = offset + std::mem::size_of::<libc::inotify_event>();
+                let name = &buffer[start..start + event.len as usize];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                if !name.is_empty() {
+                    let path = self.directory.join(OsStr::from_bytes(name));
+                    if !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                }
+                offset = start + event.len as usize;
+            }
+        }
+        changed
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_written_files_once() {
+        let directory = std::env::temp_dir().join(format!("watch-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let watcher = FileWatcher::new(&directory).unwrap();
+        assert!(watcher.changed().is_empty());
+
+        std::fs::write(directory.join("a.frag"), "void main() {}").unwrap();
+        std::fs::write(directory.join("a.frag"), "void main() { }").unwrap();
+        std::fs::write(directory.join("b.vert"), "void main() {}").unwrap();
+        let changed = watcher.changed();
+        assert_eq!(changed, vec![directory.join("a.frag"), directory.join("b.vert")]);
+        assert!(watcher.changed().is_empty());
+
+        // A file being written shows up once it is closed, and one renamed into place right away.
+        let mut file = std::fs::File::create(directory.join("c.frag")).unwrap();
+        assert!(watcher.changed().is_empty());
+        std::io::Write::write_all(&mut file, b"void main() {}").unwrap();
+        drop(file);
+        assert_eq!(watcher.changed(), vec![directory.join("c.frag")]);
+        let saved = directory.with_extension("tmp");
+        std::fs::write(&saved, "void main() {}").unwrap();
+        std::fs::rename(&saved, directory.join("d.frag")).unwrap();
+        assert_eq!(watcher.changed(), vec![directory.join("d.frag")]);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
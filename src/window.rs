@@ -1,35 +1,209 @@
-use std::ffi::CStr;
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use x11::glx::*;
 use x11::xlib::*;
-use crate::gl;
+use crate::capture::{self, Recorder, RecordingOutput};
+use crate::context::{self, GlConfig, GlProfile};
+use crate::image::Image;
+use crate::state::{ClearState, RenderState, Viewport};
+use crate::input::{Event, InputState, KeySym, MouseButton};
 
 pub struct Window {
     x11d: *mut Display,
     x11w: core::ffi::c_ulong,
-    wm_delete_message: core::ffi::c_ulong
+    wm_delete_message: core::ffi::c_ulong,
+    colormap: Colormap,
+    fb_config: GLXFBConfig,
+    gl_config: GlConfig,
+    context: Cell<GLXContext>,
+    clear: Cell<ClearState>,
+    size: Cell<(u32, u32)>,
+    resized: Cell<bool>,
+    input: RefCell<InputState>,
+    recorder: RefCell<Option<Recorder>>,
 }
 
-impl Window {
-    pub fn new(desktop: bool) -> Self {
+/// `GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB`, missing from the x11 crate.
+const GLX_FRAMEBUFFER_SRGB_CAPABLE: i32 = 0x20b2;
+
+/// Set by `catch_context_error` while `Window::init_glx` creates the context.
+static CONTEXT_ERROR: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn catch_context_error(_display: *mut Display, _event: *mut XErrorEvent) -> core::ffi::c_int {
+    CONTEXT_ERROR.store(true, Ordering::Relaxed);
+    0
+}
+
+type CreateContextAttribs = unsafe extern "C" fn(*mut Display, GLXFBConfig, GLXContext, Bool, *const i32) -> GLXContext;
+type SwapInterval = unsafe extern "C" fn(*mut Display, GLXDrawable, i32);
+
+/// Settings for a new `Window`.
+#[derive(Debug, Clone)]
+pub struct WindowBuilder {
+    width: u32,
+    height: u32,
+    title: String,
+    position: Option<(i32, i32)>,
+    resizable: bool,
+    fullscreen: bool,
+    decorations: bool,
+    desktop: bool,
+    gl_config: GlConfig,
+}
+
+impl Default for WindowBuilder {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            title: String::new(),
+            position: None,
+            resizable: true,
+            fullscreen: false,
+            decorations: true,
+            desktop: false,
+            gl_config: GlConfig::default(),
+        }
+    }
+}
+
+impl WindowBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Asks the window manager to place the window at `x`, `y` instead of choosing a spot.
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// Makes the window a desktop background, below every other window.
+    pub fn desktop(mut self, desktop: bool) -> Self {
+        self.desktop = desktop;
+        self
+    }
+
+    /// The OpenGL context `Window::init_glx` creates. The framebuffer format is chosen here,
+    /// since the window has to be created with a matching visual.
+    pub fn gl_config(mut self, config: GlConfig) -> Self {
+        self.gl_config = config;
+        self
+    }
+
+    pub fn build(self) -> Result<Window, String> {
+        let title = CString::new(self.title).map_err(|_| "Window title contains a nul byte.".to_string())?;
         unsafe {
             let x11d = XOpenDisplay(core::ptr::null());
-            let x11w = XCreateSimpleWindow(
+            if x11d.is_null() {
+                return Err("Couldn't open display.".to_string());
+            }
+            let fb_config = match choose_fb_config(x11d, &self.gl_config) {
+                Ok(fb_config) => fb_config,
+                Err(e) => {
+                    XCloseDisplay(x11d);
+                    return Err(e);
+                }
+            };
+            let visual = glXGetVisualFromFBConfig(x11d, fb_config);
+            if visual.is_null() {
+                XCloseDisplay(x11d);
+                return Err("Couldn't get a visual for the framebuffer config.".to_string());
+            }
+            let root = XDefaultRootWindow(x11d);
+            let colormap = XCreateColormap(x11d, root, (*visual).visual, AllocNone);
+            let mut attributes: XSetWindowAttributes = std::mem::zeroed();
+            attributes.colormap = colormap;
+            attributes.event_mask = StructureNotifyMask | KeyPressMask | KeyReleaseMask | ButtonPressMask
+                | ButtonReleaseMask | PointerMotionMask | FocusChangeMask;
+            let (x, y) = self.position.unwrap_or((0, 0));
+            let x11w = XCreateWindow(
                 x11d,
-                XDefaultRootWindow(x11d),
-                0,
-                0,
-                1920,
-                1080,
-                0,
+                root,
+                x,
+                y,
+                self.width,
+                self.height,
                 0,
-                XWhitePixel(x11d, XDefaultScreen(x11d)),
+                (*visual).depth,
+                InputOutput as _,
+                (*visual).visual,
+                CWColormap | CWBorderPixel | CWEventMask,
+                &mut attributes,
             );
+            XFree(visual as *mut _);
+            // Held keys repeat as presses only, instead of release/press pairs.
+            XkbSetDetectableAutoRepeat(x11d, True, core::ptr::null_mut());
 
-            let cstring = CStr::from_bytes_with_nul(b"WM_DELETE_WINDOW\0").unwrap();
+            let cstring = c"WM_DELETE_WINDOW";
             let mut wm_delete_message = XInternAtom(x11d, cstring.as_ptr(), False);
             XSetWMProtocols(x11d, x11w, &mut wm_delete_message, 1);
 
-            if desktop {
-                let cstring = CStr::from_bytes_with_nul(b"_NET_WM_WINDOW_TYPE\0").unwrap();
+            XStoreName(x11d, x11w, title.as_ptr());
+            let name = XInternAtom(x11d, c"_NET_WM_NAME".as_ptr(), False);
+            let utf8 = XInternAtom(x11d, c"UTF8_STRING".as_ptr(), False);
+            let bytes = title.as_bytes();
+            XChangeProperty(x11d, x11w, name, utf8, 8, PropModeReplace, bytes.as_ptr(), bytes.len() as _);
+
+            let mut hints: XSizeHints = std::mem::zeroed();
+            if self.position.is_some() {
+                hints.flags |= USPosition | PPosition;
+                hints.x = x;
+                hints.y = y;
+            }
+            if !self.resizable {
+                hints.flags |= PMinSize | PMaxSize;
+                hints.min_width = self.width as _;
+                hints.max_width = self.width as _;
+                hints.min_height = self.height as _;
+                hints.max_height = self.height as _;
+            }
+            XSetWMNormalHints(x11d, x11w, &mut hints);
+
+            if !self.decorations {
+                // Motif hints are the de facto way to ask window managers for no decorations:
+                // flags, functions, decorations, input mode, status.
+                let motif_hints = XInternAtom(x11d, c"_MOTIF_WM_HINTS".as_ptr(), False);
+                let data: [core::ffi::c_long; 5] = [2, 0, 0, 0, 0];
+                XChangeProperty(x11d, x11w, motif_hints, motif_hints, 32, PropModeReplace, data.as_ptr() as *const u8, 5);
+            }
+
+            if self.fullscreen {
+                // Read by the window manager when the window is mapped.
+                let state = XInternAtom(x11d, c"_NET_WM_STATE".as_ptr(), False);
+                let fullscreen = XInternAtom(x11d, c"_NET_WM_STATE_FULLSCREEN".as_ptr(), False);
+                XChangeProperty(x11d, x11w, state, XA_ATOM, 32, PropModeReplace, &fullscreen as *const _ as *const u8, 1);
+            }
+
+            if self.desktop {
+                let cstring = c"_NET_WM_WINDOW_TYPE";
 
                 let window_type = XInternAtom(
                     x11d,
@@ -37,7 +211,7 @@ impl Window {
                     False,
                 );
 
-                let cstring2 = CStr::from_bytes_with_nul(b"_NET_WM_WINDOW_TYPE_DESKTOP\0").unwrap();
+                let cstring2 = c"_NET_WM_WINDOW_TYPE_DESKTOP";
 
                 let desktop = XInternAtom(
                     x11d,
@@ -52,13 +226,73 @@ impl Window {
                     XA_ATOM,
                     32,
                     PropModeReplace,
-                    std::mem::transmute(&desktop),
+                    &desktop as *const _ as *const u8,
                     1,
                 );
             }
             XClearWindow(x11d, x11w);
 
-            Window { x11d, x11w, wm_delete_message }
+            Ok(Window {
+                x11d,
+                x11w,
+                wm_delete_message,
+                colormap,
+                fb_config,
+                gl_config: self.gl_config,
+                context: Cell::new(core::ptr::null_mut()),
+                clear: Cell::new(ClearState::default()),
+                size: Cell::new((self.width, self.height)),
+                resized: Cell::new(false),
+                input: RefCell::new(InputState::new()),
+                recorder: RefCell::new(None),
+            })
+        }
+    }
+}
+
+impl Window {
+    pub fn new(desktop: bool) -> Result<Self, String> {
+        WindowBuilder::new().desktop(desktop).build()
+    }
+
+    /// Current size of the window in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        self.size.get()
+    }
+
+    pub fn aspect(&self) -> f32 {
+        let (width, height) = self.size();
+        width as f32 / height.max(1) as f32
+    }
+
+    /// The new size if the window was resized since the last call. The GL viewport has already
+    /// been updated; cameras should update their aspect ratio.
+    pub fn resized(&self) -> Option<(u32, u32)> {
+        self.resized.replace(false).then(|| self.size())
+    }
+
+    /// Switches fullscreen on or off through the window manager.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        unsafe {
+            let state = XInternAtom(self.x11d, c"_NET_WM_STATE".as_ptr(), False);
+            let atom = XInternAtom(self.x11d, c"_NET_WM_STATE_FULLSCREEN".as_ptr(), False);
+            let mut event: XEvent = std::mem::zeroed();
+            event.client_message.type_ = ClientMessage;
+            event.client_message.window = self.x11w;
+            event.client_message.message_type = state;
+            event.client_message.format = 32;
+            // _NET_WM_STATE_REMOVE = 0, _NET_WM_STATE_ADD = 1.
+            event.client_message.data.set_long(0, fullscreen as _);
+            event.client_message.data.set_long(1, atom as _);
+            event.client_message.data.set_long(3, 1);
+            XSendEvent(
+                self.x11d,
+                XDefaultRootWindow(self.x11d),
+                False,
+                SubstructureRedirectMask | SubstructureNotifyMask,
+                &mut event,
+            );
+            XFlush(self.x11d);
         }
     }
 
@@ -68,61 +302,257 @@ impl Window {
         }
     }
 
+    /// Creates the OpenGL context configured with `WindowBuilder::gl_config` and makes it current.
     pub fn init_glx(&self) -> Result<(), String> {
+        let config = &self.gl_config;
         unsafe {
-            let mut glx_attributes = [
-                //x11::glx::GLX_X_RENDERABLE, gl::TRUE as c_int,
-                x11::glx::GLX_DRAWABLE_TYPE, x11::glx::GLX_WINDOW_BIT,
-                x11::glx::GLX_RENDER_TYPE, x11::glx::GLX_RGBA_BIT,
-                x11::glx::GLX_X_VISUAL_TYPE, x11::glx::GLX_TRUE_COLOR,
-                x11::glx::GLX_RED_SIZE, 8,
-                x11::glx::GLX_GREEN_SIZE, 8,
-                x11::glx::GLX_BLUE_SIZE, 8,
-                x11::glx::GLX_ALPHA_SIZE, 8,
-                x11::glx::GLX_DEPTH_SIZE, 24,
-                x11::glx::GLX_STENCIL_SIZE, 8,
-                x11::glx::GLX_DOUBLEBUFFER, gl::TRUE as core::ffi::c_int,
-                //GLX_SAMPLE_BUFFERS  , 1,
-                //GLX_SAMPLES         , 4,
-                gl::NONE as core::ffi::c_int
+            let extensions = glXQueryExtensionsString(self.x11d, XDefaultScreen(self.x11d));
+            let extensions = CStr::from_ptr(extensions).to_string_lossy();
+            for extension in ["GLX_ARB_create_context", "GLX_ARB_create_context_profile"] {
+                if !extensions.split_whitespace().any(|e| e == extension) {
+                    return Err(format!("Couldn't create an {} context: {} isn't supported.", config, extension));
+                }
+            }
+            let create = glXGetProcAddressARB(c"glXCreateContextAttribsARB".as_ptr() as *const u8)
+                .ok_or_else(|| format!("Couldn't create an {} context: glXCreateContextAttribsARB is missing.", config))?;
+            let create = std::mem::transmute::<unsafe extern "C" fn(), CreateContextAttribs>(create);
+
+            let profile = match config.profile {
+                GlProfile::Core => arb::GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
+                GlProfile::Compatibility => arb::GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
+            };
+            let flags = if config.debug { arb::GLX_CONTEXT_DEBUG_BIT_ARB } else { 0 };
+            let context_attributes = [
+                arb::GLX_CONTEXT_MAJOR_VERSION_ARB, config.version.0 as i32,
+                arb::GLX_CONTEXT_MINOR_VERSION_ARB, config.version.1 as i32,
+                arb::GLX_CONTEXT_PROFILE_MASK_ARB, profile,
+                arb::GLX_CONTEXT_FLAGS_ARB, flags,
+                0,
             ];
-            let glx_p = glx_attributes.as_mut_ptr();
-            let vis = x11::glx::glXChooseVisual(self.x11d, 0, glx_p);
-            if vis.is_null() {
-                return Err("Couldn't choose visual info.".to_string());
+            // Unsupported versions fail with an X error, which Xlib's default handler turns into an
+            // exit. Catch it instead, waiting for the reply before putting the old handler back.
+            XSync(self.x11d, False);
+            CONTEXT_ERROR.store(false, Ordering::Relaxed);
+            let previous = XSetErrorHandler(Some(catch_context_error));
+            let ctx = create(self.x11d, self.fb_config, core::ptr::null_mut(), True, context_attributes.as_ptr());
+            XSync(self.x11d, False);
+            XSetErrorHandler(previous);
+            if CONTEXT_ERROR.load(Ordering::Relaxed) || ctx.is_null() {
+                if !ctx.is_null() {
+                    glXDestroyContext(self.x11d, ctx);
+                }
+                return Err(format!("Couldn't create an {} context. The driver may not support this version.", config));
             }
-            let ctx = x11::glx::glXCreateContext(self.x11d, vis, 0 as x11::glx::GLXContext, 1);
-            x11::glx::glXMakeCurrent(self.x11d, self.x11w, ctx);
+            if glXMakeCurrent(self.x11d, self.x11w, ctx) == False {
+                glXDestroyContext(self.x11d, ctx);
+                return Err(format!("Couldn't make the {} context current.", config));
+            }
+            self.context.set(ctx);
+            context::apply(config);
 
-            gl::ClearColor(1.0, 0.0, 0.0, 1.0);
-            gl::Enable(gl::CULL_FACE);
+            let (width, height) = self.size();
+            Viewport::new(width as _, height as _).apply();
+            RenderState::default().apply();
+            self.clear_state().clear();
         }
         Ok(())
     }
 
-    pub fn swap_buffers(&self, fps: u64) {
+    /// Presents the frame and clears the buffers for the next one. Pace frames with vsync or a
+    /// `FrameClock`. While recording, also captures the frame.
+    pub fn swap_buffers(&self) {
+        if let Some(recorder) = self.recorder.borrow_mut().as_mut() {
+            let (width, height) = self.size();
+            recorder.capture(width, height);
+        }
         unsafe {
-            std::thread::sleep(core::time::Duration::from_micros(1_000_000 / fps));
             x11::glx::glXSwapBuffers(self.x11d, self.x11w);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
         }
+        self.clear_state().clear();
+    }
+
+    /// Reads back the frame drawn so far. Call it after drawing and before `swap_buffers`, which
+    /// leaves the back buffer undefined. Waits for drawing to finish; use a recording to capture
+    /// every frame without stalling.
+    pub fn capture_frame(&self) -> Image {
+        let (width, height) = self.size();
+        capture::read_pixels(width, height)
     }
 
+    /// Captures every frame from now on in `swap_buffers`. The pixels are read back
+    /// asynchronously a few frames later, then encoded and written on a separate thread.
+    pub fn start_recording(&self, output: RecordingOutput) -> Result<(), String> {
+        if self.is_recording() {
+            return Err("Couldn't start recording: already recording.".to_string());
+        }
+        *self.recorder.borrow_mut() = Some(Recorder::new(output)?);
+        Ok(())
+    }
+
+    /// Writes the frames still being read back and ends the recording. Returns the number of
+    /// frames written, or the error that stopped the recording.
+    pub fn stop_recording(&self) -> Result<u64, String> {
+        let recorder = self.recorder.borrow_mut().take().ok_or("Couldn't stop recording: not recording.")?;
+        recorder.finish()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.borrow().is_some()
+    }
+
+    pub fn clear_state(&self) -> ClearState {
+        self.clear.get()
+    }
+
+    /// Values `swap_buffers` clears the color, depth and stencil buffers to.
+    pub fn set_clear_state(&self, clear: ClearState) {
+        self.clear.set(clear);
+    }
+
+    /// Turns vsync on or off through `GLX_EXT_swap_control`. Needs the context from `init_glx`.
+    pub fn set_vsync(&self, enabled: bool) -> Result<(), String> {
+        unsafe {
+            let extensions = glXQueryExtensionsString(self.x11d, XDefaultScreen(self.x11d));
+            if !CStr::from_ptr(extensions).to_string_lossy().split_whitespace().any(|e| e == "GLX_EXT_swap_control") {
+                return Err("Couldn't set vsync: GLX_EXT_swap_control isn't supported.".to_string());
+            }
+            let swap_interval = glXGetProcAddressARB(c"glXSwapIntervalEXT".as_ptr() as *const u8)
+                .ok_or_else(|| "Couldn't set vsync: glXSwapIntervalEXT is missing.".to_string())?;
+            let swap_interval = std::mem::transmute::<unsafe extern "C" fn(), SwapInterval>(swap_interval);
+            swap_interval(self.x11d, self.x11w, enabled as i32);
+        }
+        Ok(())
+    }
+
+    /// Handles every pending event, returning true if the window was asked to close. Other
+    /// events only reach `input()`; use `poll_events` to see them.
     pub fn close(&self) -> bool {
+        self.poll_events().contains(&Event::Close)
+    }
+
+    /// Drains the event queue. Also starts a new frame of `input()`, so call it once per frame.
+    #[allow(non_upper_case_globals)]
+    pub fn poll_events(&self) -> Vec<Event> {
+        let mut input = self.input.borrow_mut();
+        input.begin_frame();
+        let mut events = Vec::new();
         unsafe {
-            let mut event = std::mem::zeroed();
-            if XPending(self.x11d) == 0 { return false; }
-            XNextEvent(self.x11d, &mut event);
-            #[allow(non_upper_case_globals)]
-            match event.get_type() {
-                ClientMessage => {
-                    if event.client_message.data.get_long(0) == self.wm_delete_message as i64 {
-                        return true;
+            while XPending(self.x11d) != 0 {
+                let mut event: XEvent = std::mem::zeroed();
+                XNextEvent(self.x11d, &mut event);
+                let translated = match event.get_type() {
+                    ClientMessage if event.client_message.data.get_long(0) == self.wm_delete_message as i64 => {
+                        Some(Event::Close)
+                    }
+                    ConfigureNotify => {
+                        let (width, height) = (event.configure.width as u32, event.configure.height as u32);
+                        self.configure(width, height).then_some(Event::Resize { width, height })
                     }
+                    KeyPress => {
+                        let keysym = XLookupKeysym(&mut event.key, 0) as KeySym;
+                        Some(Event::KeyDown { keysym, repeat: input.is_key_down(keysym) })
+                    }
+                    KeyRelease => Some(Event::KeyUp { keysym: XLookupKeysym(&mut event.key, 0) as KeySym }),
+                    MotionNotify => Some(Event::MouseMove { x: event.motion.x as f32, y: event.motion.y as f32 }),
+                    ButtonPress | ButtonRelease => {
+                        let pressed = event.get_type() == ButtonPress;
+                        // The wheel shows up as buttons 4 to 7, pressed and released at once.
+                        match event.button.button {
+                            4 => pressed.then_some(Event::Scroll { x: 0.0, y: 1.0 }),
+                            5 => pressed.then_some(Event::Scroll { x: 0.0, y: -1.0 }),
+                            6 => pressed.then_some(Event::Scroll { x: -1.0, y: 0.0 }),
+                            7 => pressed.then_some(Event::Scroll { x: 1.0, y: 0.0 }),
+                            button => Some(Event::MouseButton { button: MouseButton::from_x11(button), pressed }),
+                        }
+                    }
+                    FocusIn => Some(Event::Focus(true)),
+                    FocusOut => Some(Event::Focus(false)),
+                    _ => None,
+                };
+                if let Some(event) = translated {
+                    input.handle(&event);
+                    events.push(event);
                 }
-                _ => {}
             }
-            false
         }
+        events
+    }
+
+    /// Snapshot of the keyboard and mouse as of the last `poll_events`.
+    pub fn input(&self) -> InputState {
+        self.input.borrow().clone()
+    }
+
+    /// Returns whether the size changed.
+    fn configure(&self, width: u32, height: u32) -> bool {
+        if (width, height) == self.size() {
+            return false;
+        }
+        self.size.set((width, height));
+        self.resized.set(true);
+        Viewport::new(width as _, height as _).apply();
+        true
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        // The recording needs the context to read back its last frames.
+        if self.is_recording() {
+            if let Err(e) = self.stop_recording() {
+                eprintln!("{}", e);
+            }
+        }
+        unsafe {
+            if !self.context.get().is_null() {
+                glXMakeCurrent(self.x11d, 0, core::ptr::null_mut());
+                glXDestroyContext(self.x11d, self.context.get());
+            }
+            XDestroyWindow(self.x11d, self.x11w);
+            XFreeColormap(self.x11d, self.colormap);
+            XCloseDisplay(self.x11d);
+        }
+    }
+}
+
+/// Best framebuffer config for `config`: RGBA8, 24-bit depth, 8-bit stencil, double buffered.
+unsafe fn choose_fb_config(x11d: *mut Display, config: &GlConfig) -> Result<GLXFBConfig, String> {
+    let mut attributes = vec![
+        GLX_X_RENDERABLE, True,
+        GLX_DRAWABLE_TYPE, GLX_WINDOW_BIT,
+        GLX_RENDER_TYPE, GLX_RGBA_BIT,
+        GLX_X_VISUAL_TYPE, GLX_TRUE_COLOR,
+        GLX_RED_SIZE, 8,
+        GLX_GREEN_SIZE, 8,
+        GLX_BLUE_SIZE, 8,
+        GLX_ALPHA_SIZE, 8,
+        GLX_DEPTH_SIZE, 24,
+        GLX_STENCIL_SIZE, 8,
+        GLX_DOUBLEBUFFER, True,
+    ];
+    if config.samples > 0 {
+        attributes.extend([GLX_SAMPLE_BUFFERS, 1, GLX_SAMPLES, config.samples as i32]);
+    }
+    if config.srgb {
+        attributes.extend([GLX_FRAMEBUFFER_SRGB_CAPABLE, True]);
+    }
+    attributes.push(0);
+
+    let mut count = 0;
+    let configs = glXChooseFBConfig(x11d, XDefaultScreen(x11d), attributes.as_ptr(), &mut count);
+    if configs.is_null() || count == 0 {
+        let mut features = Vec::new();
+        if config.samples > 0 {
+            features.push(format!("{}x MSAA", config.samples));
+        }
+        if config.srgb {
+            features.push("sRGB".to_string());
+        }
+        let features = if features.is_empty() { String::new() } else { format!(" with {}", features.join(" and ")) };
+        return Err(format!("Couldn't find a framebuffer config{}.", features));
     }
-}
\ No newline at end of file
+    // Configs come sorted best first.
+    let fb_config = *configs;
+    XFree(configs as *mut _);
+    Ok(fb_config)
+}